@@ -0,0 +1,77 @@
+// Times typing into one very long row: `cargo bench --bench row_insert`.
+// `Row::insert` splices at a byte offset; the rebuild column is how it used
+// to work, copying every grapheme into a new string on each keystroke. The
+// document column types through `Document::insert`, undo history and all.
+use rtext::{Document, Position, Row};
+use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
+
+const ROW_LEN: usize = 100_000;
+const INSERTS: usize = 1_000;
+
+fn rebuild_insert(string: &str, at: usize, c: char) -> String {
+    let mut result = String::new();
+    for (index, grapheme) in string.graphemes(true).enumerate() {
+        if index == at {
+            result.push(c);
+        }
+        result.push_str(grapheme);
+    }
+    result
+}
+
+fn splice(text: &str) -> Duration {
+    let mut row = Row::from(text);
+    let start = Instant::now();
+    for offset in 0..INSERTS {
+        row.insert(ROW_LEN / 2 + offset, 'x');
+    }
+    let elapsed = start.elapsed();
+    assert_eq!(row.len(), ROW_LEN + INSERTS);
+    elapsed
+}
+
+fn document(text: &str) -> Duration {
+    let mut document = Document::default();
+    document.insert_text(&Position::default(), text).unwrap();
+    document.end_undo_group();
+    let start = Instant::now();
+    for offset in 0..INSERTS {
+        let at = Position {
+            x: ROW_LEN / 2 + offset,
+            y: 0,
+        };
+        document.insert(&at, 'x').unwrap();
+    }
+    let elapsed = start.elapsed();
+    assert_eq!(document.row(0).unwrap().len(), ROW_LEN + INSERTS);
+    elapsed
+}
+
+fn rebuild(text: &str) -> Duration {
+    let mut string = text.to_string();
+    let start = Instant::now();
+    for offset in 0..INSERTS {
+        string = rebuild_insert(&string, ROW_LEN / 2 + offset, 'x');
+    }
+    let elapsed = start.elapsed();
+    assert_eq!(string.graphemes(true).count(), ROW_LEN + INSERTS);
+    elapsed
+}
+
+fn main() {
+    println!("{INSERTS} inserts into the middle of a {ROW_LEN}-grapheme row");
+    println!(
+        "{:<12}{:>12}{:>12}{:>12}",
+        "row", "splice", "document", "rebuild"
+    );
+    for (name, grapheme) in [("ASCII", "a"), ("multi-byte", "é")] {
+        let text = grapheme.repeat(ROW_LEN);
+        println!(
+            "{name:<12}{:>12.1?}{:>12.1?}{:>12.1?}",
+            splice(&text),
+            document(&text),
+            rebuild(&text)
+        );
+    }
+}
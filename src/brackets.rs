@@ -0,0 +1,121 @@
+use crate::{HighlightingOptions, Position, Row};
+use unicode_segmentation::UnicodeSegmentation;
+
+// Returns the positions of brackets without a partner.
+pub fn unmatched(rows: &[Row], opts: &HighlightingOptions) -> Vec<Position> {
+    pair_up(rows, opts).1
+}
+
+// Returns where the partner of the bracket at `at` is, if it has one.
+pub fn partner(rows: &[Row], opts: &HighlightingOptions, at: &Position) -> Option<Position> {
+    pair_up(rows, opts).0.into_iter().find_map(|(open, close)| {
+        if open == *at {
+            Some(close)
+        } else if close == *at {
+            Some(open)
+        } else {
+            None
+        }
+    })
+}
+
+// Pairs up brackets, skipping strings, character literals and comments the
+// same way the highlighter does. Returns the pairs and the brackets left
+// without a partner.
+fn pair_up(rows: &[Row], opts: &HighlightingOptions) -> (Vec<(Position, Position)>, Vec<Position>) {
+    let mut pairs = Vec::new();
+    let mut unmatched = Vec::new();
+    let mut open: Vec<(char, Position)> = Vec::new();
+    let mut in_ml_comment = false;
+    for (y, row) in rows.iter().enumerate() {
+        let graphemes: Vec<&str> = row.as_str().graphemes(true).collect();
+        let mut x = 0;
+        while let Some(grapheme) = graphemes.get(x) {
+            if in_ml_comment {
+                if starts_with_at(&graphemes, x, "*/") {
+                    in_ml_comment = false;
+                    x = x.saturating_add(1);
+                }
+            } else if opts.multiline_comments() && starts_with_at(&graphemes, x, "/*") {
+                in_ml_comment = true;
+                x = x.saturating_add(1);
+            } else if opts.comments()
+                && opts
+                    .comment_starts()
+                    .iter()
+                    .any(|start| starts_with_at(&graphemes, x, start))
+            {
+                break;
+            } else if opts.strings()
+                && (*grapheme == "\"" || (*grapheme == "'" && opts.single_quoted_strings()))
+            {
+                x = skip_quoted(&graphemes, x, opts);
+            } else if opts.characters() && *grapheme == "'" {
+                x = skip_character(&graphemes, x);
+            } else if let Some(c @ ('(' | '[' | '{')) = grapheme.chars().next() {
+                open.push((c, Position { x, y }));
+            } else if let Some(c @ (')' | ']' | '}')) = grapheme.chars().next() {
+                match open.last() {
+                    Some((opening, position)) if closing_for(*opening) == c => {
+                        pairs.push((*position, Position { x, y }));
+                        open.pop();
+                    }
+                    _ => unmatched.push(Position { x, y }),
+                }
+            }
+            x = x.saturating_add(1);
+        }
+    }
+    unmatched.extend(open.into_iter().map(|(_, position)| position));
+    (pairs, unmatched)
+}
+
+fn closing_for(c: char) -> char {
+    match c {
+        '(' => ')',
+        '[' => ']',
+        _ => '}',
+    }
+}
+
+fn skip_quoted(graphemes: &[&str], start: usize, opts: &HighlightingOptions) -> usize {
+    let escapes = graphemes
+        .get(start)
+        .and_then(|quote| quote.chars().next())
+        .map_or(true, |quote| opts.escapes_in(quote));
+    let mut x = start.saturating_add(1);
+    while let Some(grapheme) = graphemes.get(x) {
+        if *grapheme == "\\" && escapes {
+            x = x.saturating_add(1);
+        } else if Some(grapheme) == graphemes.get(start) {
+            return x;
+        }
+        x = x.saturating_add(1);
+    }
+    x
+}
+
+// Only skips real character literals like 'a' or '\n'; a lone quote such
+// as a Rust lifetime is left alone.
+fn skip_character(graphemes: &[&str], start: usize) -> usize {
+    let close = if graphemes.get(start.saturating_add(1)) == Some(&"\\") {
+        graphemes
+            .iter()
+            .skip(start.saturating_add(2))
+            .position(|grapheme| *grapheme == "'")
+            .map(|offset| start.saturating_add(2).saturating_add(offset))
+    } else {
+        Some(start.saturating_add(2)).filter(|close| graphemes.get(*close) == Some(&"'"))
+    };
+    close.unwrap_or(start)
+}
+
+fn starts_with_at(graphemes: &[&str], index: usize, pattern: &str) -> bool {
+    !pattern.is_empty()
+        && pattern.chars().enumerate().all(|(offset, c)| {
+            graphemes
+                .get(index.saturating_add(offset))
+                .and_then(|grapheme| grapheme.chars().next())
+                == Some(c)
+        })
+}
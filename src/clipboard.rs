@@ -0,0 +1,37 @@
+use crate::filter;
+#[cfg(feature = "system-clipboard")]
+use std::env;
+
+// Copy and paste commands for whichever clipboard this session has. The
+// copy tools linger in the background to serve the clipboard, so their
+// output goes nowhere rather than into a pipe that never closes.
+#[cfg(feature = "system-clipboard")]
+fn commands() -> Option<(&'static str, &'static str)> {
+    if cfg!(target_os = "macos") {
+        Some(("pbcopy", "pbpaste"))
+    } else if env::var_os("WAYLAND_DISPLAY").is_some() {
+        Some(("wl-copy >/dev/null 2>&1", "wl-paste --no-newline"))
+    } else if env::var_os("DISPLAY").is_some() {
+        Some((
+            "xclip -selection clipboard >/dev/null 2>&1",
+            "xclip -selection clipboard -o",
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(feature = "system-clipboard"))]
+fn commands() -> Option<(&'static str, &'static str)> {
+    None
+}
+
+pub fn copy(text: &str) -> Result<(), String> {
+    let (copy, _) = commands().ok_or_else(|| "no system clipboard".to_string())?;
+    filter::pipe(copy, text).map(|_| ())
+}
+
+pub fn paste() -> Result<String, String> {
+    let (_, paste) = commands().ok_or_else(|| "no system clipboard".to_string())?;
+    filter::pipe(paste, "")
+}
@@ -0,0 +1,512 @@
+use crate::Row;
+use std::{env, fs, path::Path};
+
+const EDITORCONFIG_FILE_NAME: &str = ".editorconfig";
+const CONFIG_FILE_NAME: &str = ".rtextrc";
+const DEFAULT_DICTIONARY_PATH: &str = "/usr/share/dict/words";
+const INDENT_SAMPLE_LINES: usize = 1000;
+const DEFAULT_SWAP_INTERVAL: u64 = 30;
+const DEFAULT_QUIT_TIMES: u8 = 3;
+const DEFAULT_MINIMAP_WIDTH: usize = 12;
+const DEFAULT_BAR_ROWS: u16 = 2;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum IndentStyle {
+    Tab,
+    Space,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct Settings {
+    pub indent_style: Option<IndentStyle>,
+    pub indent_size: Option<usize>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub spell_check: bool,
+    pub dictionary_path: String,
+    pub format_on_save: bool,
+    pub format_commands: Vec<(String, String)>,
+    pub companions: Vec<(String, String)>,
+    pub welcome_message: Option<String>,
+    pub swap_interval: u64,
+    pub auto_pairs: bool,
+    pub crosshair: bool,
+    pub file_types: Vec<(String, String)>,
+    pub page_scroll_lines: Option<usize>,
+    pub quit_times: u8,
+    pub snippets: Vec<(String, String)>,
+    pub preserve_line_endings: bool,
+    pub edge_markers: bool,
+    pub minimap: bool,
+    pub minimap_width: usize,
+    pub bar_rows: u16,
+    pub continue_comments: bool,
+    pub cursor_shape: bool,
+    pub case_sensitive: Option<bool>,
+}
+
+#[derive(Default, Debug)]
+struct Section {
+    name: String,
+    properties: Vec<(String, String)>,
+}
+
+#[derive(Default, Debug)]
+struct Ini {
+    preamble: Vec<(String, String)>,
+    sections: Vec<Section>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            spell_check: true,
+            dictionary_path: DEFAULT_DICTIONARY_PATH.to_string(),
+            format_on_save: false,
+            format_commands: vec![("rust".to_string(), "rustfmt --edition 2021".to_string())],
+            companions: [
+                ("*.c", "*.h"),
+                ("*.cpp", "*.hpp"),
+                ("src/*.rs", "tests/*.rs"),
+            ]
+            .iter()
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect(),
+            welcome_message: None,
+            swap_interval: DEFAULT_SWAP_INTERVAL,
+            auto_pairs: false,
+            crosshair: false,
+            file_types: Vec::new(),
+            page_scroll_lines: None,
+            quit_times: DEFAULT_QUIT_TIMES,
+            snippets: Vec::new(),
+            preserve_line_endings: false,
+            edge_markers: true,
+            minimap: false,
+            minimap_width: DEFAULT_MINIMAP_WIDTH,
+            bar_rows: DEFAULT_BAR_ROWS,
+            continue_comments: false,
+            cursor_shape: true,
+            case_sensitive: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let mut config = Self::default();
+        let content = env::var_os("HOME")
+            .map(|home| Path::new(&home).join(CONFIG_FILE_NAME))
+            .and_then(|path| fs::read_to_string(path).ok());
+        if let Some(content) = content {
+            let ini = Ini::parse(&content);
+            let mut companions = Vec::new();
+            for (key, value) in &ini.preamble {
+                config.set(key, value);
+            }
+            for section in &ini.sections {
+                if section.name == "format" {
+                    for (file_type, command) in &section.properties {
+                        config.set_format_command(file_type, command);
+                    }
+                } else if section.name == "companions" {
+                    companions.extend(section.properties.iter().cloned());
+                } else if section.name == "file_types" {
+                    config.file_types.extend(section.properties.iter().cloned());
+                } else if section.name == "snippets" {
+                    for (name, body) in &section.properties {
+                        config.snippets.push((name.clone(), unescape(body)));
+                    }
+                }
+            }
+            companions.append(&mut config.companions);
+            config.companions = companions;
+        }
+        config
+    }
+
+    // Each pair maps a pattern with a single `*` to another one, in both
+    // directions. Patterns containing a `/` match trailing path components,
+    // the others only the file name.
+    pub fn companion_paths(&self, path: &str) -> Vec<String> {
+        let mut paths = Vec::new();
+        for (from, to) in &self.companions {
+            paths.extend(substitute(from, to, path));
+            paths.extend(substitute(to, from, path));
+        }
+        paths
+    }
+
+    // Maps file name endings such as `.gyp` or `.bashrc` to the name of a
+    // built-in highlighter. The first matching entry wins.
+    pub fn file_type_for(&self, file_name: &str) -> Option<&str> {
+        self.file_types
+            .iter()
+            .find(|(ending, _)| file_name.ends_with(ending.as_str()))
+            .map(|(_, name)| name.as_str())
+    }
+
+    // Snippet names are case-insensitive, like every other key in the file.
+    pub fn snippet(&self, name: &str) -> Option<&str> {
+        self.snippets
+            .iter()
+            .find(|(snippet, _)| snippet.eq_ignore_ascii_case(name))
+            .map(|(_, body)| body.as_str())
+    }
+
+    pub fn format_command(&self, file_type: &str) -> Option<&str> {
+        if !self.format_on_save {
+            return None;
+        }
+        let file_type = file_type.to_lowercase();
+        self.format_commands
+            .iter()
+            .find(|(name, _)| *name == file_type)
+            .map(|(_, command)| command.as_str())
+            .filter(|command| !command.is_empty())
+    }
+
+    pub fn case_sensitive(&self, query: &str) -> bool {
+        self.case_sensitive
+            .unwrap_or_else(|| query.chars().any(char::is_uppercase))
+    }
+
+    fn set_format_command(&mut self, file_type: &str, command: &str) {
+        self.format_commands.retain(|(name, _)| name != file_type);
+        self.format_commands
+            .push((file_type.to_string(), command.to_string()));
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        match key {
+            "spell_check" => {
+                self.spell_check = parse_bool(&value.to_lowercase()).unwrap_or(self.spell_check);
+            }
+            "dictionary" => self.dictionary_path = value.to_string(),
+            "welcome_message" => self.welcome_message = Some(value.to_string()),
+            // 0 or an invalid count pages by a full screen again.
+            "page_scroll_lines" => {
+                self.page_scroll_lines = value.parse().ok().filter(|lines| *lines > 0);
+            }
+            // Extra Ctrl-Q presses needed to quit with unsaved changes.
+            "quit_times" => self.quit_times = value.parse().unwrap_or(self.quit_times),
+            "swap_interval" => self.swap_interval = value.parse().unwrap_or(self.swap_interval),
+            "auto_pairs" => {
+                self.auto_pairs = parse_bool(&value.to_lowercase()).unwrap_or(self.auto_pairs);
+            }
+            // Marks rows that continue past the left or right edge.
+            "edge_markers" => {
+                self.edge_markers = parse_bool(&value.to_lowercase()).unwrap_or(self.edge_markers);
+            }
+            // The status bar, then rows for the message; 0 hides both.
+            "bar_rows" => self.bar_rows = value.parse().unwrap_or(self.bar_rows),
+            "minimap" => {
+                self.minimap = parse_bool(&value.to_lowercase()).unwrap_or(self.minimap);
+            }
+            "minimap_width" => {
+                self.minimap_width = value
+                    .parse()
+                    .ok()
+                    .filter(|width| *width > 0)
+                    .unwrap_or(self.minimap_width);
+            }
+            "continue_comments" => {
+                self.continue_comments =
+                    parse_bool(&value.to_lowercase()).unwrap_or(self.continue_comments);
+            }
+            // Off for terminals that don't understand the escape sequence.
+            "cursor_shape" => {
+                self.cursor_shape = parse_bool(&value.to_lowercase()).unwrap_or(self.cursor_shape);
+            }
+            // "smart" ignores case unless the query has an uppercase letter.
+            "case_sensitive" => {
+                let value = value.to_lowercase();
+                if value == "smart" {
+                    self.case_sensitive = None;
+                } else if let Some(case_sensitive) = parse_bool(&value) {
+                    self.case_sensitive = Some(case_sensitive);
+                }
+            }
+            "crosshair" => {
+                self.crosshair = parse_bool(&value.to_lowercase()).unwrap_or(self.crosshair);
+            }
+            "preserve_line_endings" => {
+                self.preserve_line_endings =
+                    parse_bool(&value.to_lowercase()).unwrap_or(self.preserve_line_endings);
+            }
+            "format_on_save" => {
+                self.format_on_save =
+                    parse_bool(&value.to_lowercase()).unwrap_or(self.format_on_save);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Settings {
+    pub fn for_file(path: &Path) -> Self {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut configs = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            if let Ok(content) = fs::read_to_string(dir.join(EDITORCONFIG_FILE_NAME)) {
+                let config = Ini::parse(&content);
+                let root = config
+                    .preamble
+                    .iter()
+                    .any(|(key, value)| key == "root" && value.eq_ignore_ascii_case("true"));
+                configs.push((dir.to_path_buf(), config));
+                if root {
+                    break;
+                }
+            }
+        }
+
+        let mut settings = Self::default();
+        for (dir, config) in configs.iter().rev() {
+            settings.apply(config, dir, &path);
+        }
+        settings
+    }
+
+    pub fn detect_indent(&mut self, rows: &[Row]) {
+        if let Some((style, size)) = detect_indent(rows) {
+            self.indent_style = Some(style);
+            if style == IndentStyle::Space {
+                self.indent_size = Some(size);
+            }
+        }
+    }
+
+    pub fn indent_unit(&self) -> String {
+        match self.indent_style {
+            Some(IndentStyle::Space) => " ".repeat(self.indent_size.unwrap_or(4)),
+            _ => "\t".to_string(),
+        }
+    }
+
+    fn apply(&mut self, config: &Ini, dir: &Path, path: &Path) {
+        let relative = path.strip_prefix(dir).map_or_else(
+            |_| path.to_string_lossy().to_string(),
+            |relative| relative.to_string_lossy().to_string(),
+        );
+        for section in &config.sections {
+            if section_matches(&section.name, &relative) {
+                for (key, value) in &section.properties {
+                    self.set(key, &value.to_lowercase());
+                }
+            }
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        match key {
+            "indent_style" => {
+                self.indent_style = match value {
+                    "tab" => Some(IndentStyle::Tab),
+                    "space" => Some(IndentStyle::Space),
+                    _ => self.indent_style,
+                }
+            }
+            "indent_size" => {
+                if let Ok(size) = value.parse() {
+                    self.indent_size = Some(size);
+                }
+            }
+            "trim_trailing_whitespace" => {
+                self.trim_trailing_whitespace = parse_bool(value).or(self.trim_trailing_whitespace);
+            }
+            "insert_final_newline" => {
+                self.insert_final_newline = parse_bool(value).or(self.insert_final_newline);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Ini {
+    fn parse(content: &str) -> Self {
+        let mut ini = Self::default();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                ini.sections.push(Section {
+                    name: name.to_string(),
+                    properties: Vec::new(),
+                });
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                let property = (key.trim().to_lowercase(), value.trim().to_string());
+                if let Some(section) = ini.sections.last_mut() {
+                    section.properties.push(property);
+                } else {
+                    ini.preamble.push(property);
+                }
+            }
+        }
+        ini
+    }
+}
+
+fn section_matches(glob: &str, relative: &str) -> bool {
+    let text = if glob.contains('/') {
+        relative
+    } else {
+        relative.rsplit('/').next().unwrap_or(relative)
+    };
+    let glob: Vec<char> = glob.trim_start_matches('/').chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_matches(&glob, &text)
+}
+
+fn glob_matches(glob: &[char], text: &[char]) -> bool {
+    match glob.first() {
+        None => text.is_empty(),
+        Some('*') if glob.get(1) == Some(&'*') => {
+            let rest = &glob[2..];
+            (0..=text.len()).any(|skip| glob_matches(rest, &text[skip..]))
+        }
+        Some('*') => {
+            let rest = &glob[1..];
+            for skip in 0..=text.len() {
+                if glob_matches(rest, &text[skip..]) {
+                    return true;
+                }
+                if text.get(skip) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => {
+            matches!(text.first(), Some(c) if *c != '/') && glob_matches(&glob[1..], &text[1..])
+        }
+        Some('{') => {
+            if let Some(close) = glob.iter().position(|c| *c == '}') {
+                let rest = &glob[close.saturating_add(1)..];
+                let alternatives: String = glob[1..close].iter().collect();
+                alternatives.split(',').any(|alternative| {
+                    let mut candidate: Vec<char> = alternative.chars().collect();
+                    candidate.extend_from_slice(rest);
+                    glob_matches(&candidate, text)
+                })
+            } else {
+                text.first() == Some(&'{') && glob_matches(&glob[1..], &text[1..])
+            }
+        }
+        Some(c) => text.first() == Some(c) && glob_matches(&glob[1..], &text[1..]),
+    }
+}
+
+// Tabs win if clearly more lines start with a tab than with spaces and vice
+// versa. The space width is the most common change in indentation between
+// consecutive indented lines.
+#[allow(clippy::integer_arithmetic)]
+fn detect_indent(rows: &[Row]) -> Option<(IndentStyle, usize)> {
+    let (mut tabs, mut spaces) = (0_usize, 0_usize);
+    let mut widths = [0_usize; 9];
+    let mut previous = 0;
+    for row in rows.iter().take(INDENT_SAMPLE_LINES) {
+        let line = row.as_str();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        if line.starts_with('\t') {
+            tabs += 1;
+        } else if indent > 0 {
+            spaces += 1;
+        }
+        if !line.starts_with('\t') {
+            if let Some(count) = widths.get_mut(indent.abs_diff(previous)) {
+                *count += 1;
+            }
+            previous = indent;
+        }
+    }
+    if tabs > spaces.saturating_mul(2) {
+        return Some((IndentStyle::Tab, 0));
+    }
+    if spaces > tabs.saturating_mul(2) {
+        let (width, count) = widths
+            .iter()
+            .enumerate()
+            .skip(2)
+            .max_by_key(|(width, count)| (**count, usize::MAX - width))?;
+        if *count > 0 {
+            return Some((IndentStyle::Space, width));
+        }
+    }
+    None
+}
+
+// Tabs on some lines and runs of spaces on others, or a space before a tab.
+// A single leading space is left alone so " * " comment bodies don't count.
+pub fn has_mixed_indent(rows: &[Row]) -> bool {
+    let (mut tabs, mut spaces) = (false, false);
+    for row in rows {
+        let line = row.as_str();
+        let rest = line.trim_start_matches([' ', '\t']);
+        let indent = &line[..line.len().saturating_sub(rest.len())];
+        if indent.contains(" \t") {
+            return true;
+        }
+        if indent.starts_with('\t') {
+            tabs = true;
+        } else if indent.starts_with("  ") {
+            spaces = true;
+        }
+        if tabs && spaces {
+            return true;
+        }
+    }
+    false
+}
+
+fn substitute(from: &str, to: &str, path: &str) -> Option<String> {
+    let (prefix, suffix) = from.split_once('*')?;
+    let head = path.strip_suffix(suffix)?;
+    let name_start = head.rfind('/').map_or(0, |index| index.saturating_add(1));
+    for (index, _) in head[name_start..].char_indices().rev() {
+        let dir = head[..name_start.saturating_add(index)].strip_suffix(prefix);
+        if let Some(dir) = dir.filter(|dir| dir.is_empty() || dir.ends_with('/')) {
+            let stem = &head[name_start.saturating_add(index)..];
+            return Some(format!("{dir}{}", to.replacen('*', stem, 1)));
+        }
+    }
+    None
+}
+
+// Lets a one-line value spell out `\n`, `\t` and `\\`.
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
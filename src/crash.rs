@@ -0,0 +1,81 @@
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{self, Write},
+    panic,
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const LOG_FILE_NAME: &str = ".rtext.log";
+const SECONDS_PER_DAY: u64 = 86_400;
+
+static CONTEXT: Mutex<String> = Mutex::new(String::new());
+
+pub fn install_hook() {
+    panic::set_hook(Box::new(|info| {
+        let path = log_path();
+        let logged = append(&path, &info.to_string()).is_ok();
+        print!(
+            "{}{}{}RText crashed: {info}\r\n",
+            termion::clear::All,
+            termion::cursor::Goto(1, 1),
+            termion::cursor::Show
+        );
+        if logged {
+            print!("Details were written to {}\r\n", path.display());
+        }
+        let _ = io::stdout().flush();
+    }));
+}
+
+pub fn set_context(context: String) {
+    if let Ok(mut current) = CONTEXT.lock() {
+        *current = context;
+    }
+}
+
+fn log_path() -> PathBuf {
+    env::var_os("HOME")
+        .map_or_else(env::temp_dir, PathBuf::from)
+        .join(LOG_FILE_NAME)
+}
+
+fn append(path: &PathBuf, message: &str) -> Result<(), io::Error> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let context = CONTEXT
+        .lock()
+        .map(|context| context.clone())
+        .unwrap_or_default();
+    writeln!(file, "[{}] {message}", timestamp())?;
+    if !context.is_empty() {
+        writeln!(file, "    {context}")?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::integer_arithmetic, clippy::integer_division)]
+fn timestamp() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, time) = (seconds / SECONDS_PER_DAY, seconds % SECONDS_PER_DAY);
+    // Civil date from days since 1970-01-01, after Howard Hinnant's algorithm.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
@@ -0,0 +1,161 @@
+use termion::color;
+
+const MAX_TABLE_SIZE: usize = 4_000_000;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Change {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub struct Summary {
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl Summary {
+    pub fn from(changes: &[Change]) -> Self {
+        let mut summary = Self::default();
+        for change in changes {
+            match change {
+                Change::Added => summary.added = summary.added.saturating_add(1),
+                Change::Removed => summary.removed = summary.removed.saturating_add(1),
+                Change::Unchanged => (),
+            }
+        }
+        summary
+    }
+}
+
+#[allow(clippy::indexing_slicing, clippy::integer_arithmetic)]
+pub fn diff<A: PartialEq<B>, B>(old: &[A], new: &[B]) -> Vec<Change> {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut changes = vec![Change::Unchanged; prefix];
+    changes.extend(diff_middle(old_middle, new_middle));
+    changes.extend(vec![Change::Unchanged; suffix]);
+    changes
+}
+
+#[allow(clippy::indexing_slicing, clippy::integer_arithmetic)]
+fn diff_middle<A: PartialEq<B>, B>(old: &[A], new: &[B]) -> Vec<Change> {
+    let (n, m) = (old.len(), new.len());
+    if n.saturating_mul(m) > MAX_TABLE_SIZE {
+        let mut changes = vec![Change::Removed; n];
+        changes.extend(vec![Change::Added; m]);
+        return changes;
+    }
+
+    let width = m + 1;
+    let mut lcs = vec![0_usize; (n + 1) * width];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * width + j] = if old[i] == new[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::with_capacity(n + m);
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            changes.push(Change::Unchanged);
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            changes.push(Change::Removed);
+            i += 1;
+        } else {
+            changes.push(Change::Added);
+            j += 1;
+        }
+    }
+    changes.extend(vec![Change::Removed; n - i]);
+    changes.extend(vec![Change::Added; m - j]);
+    changes
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Sign {
+    None,
+    Added,
+    Modified,
+    Removed,
+}
+
+impl Sign {
+    pub fn to_symbol(self) -> char {
+        match self {
+            Sign::None => ' ',
+            Sign::Added => '+',
+            Sign::Modified => '~',
+            Sign::Removed => '_',
+        }
+    }
+
+    pub fn to_color(self) -> color::Rgb {
+        match self {
+            Sign::Added => color::Rgb(133, 153, 0),
+            Sign::Modified => color::Rgb(181, 137, 0),
+            Sign::Removed => color::Rgb(220, 50, 47),
+            Sign::None => color::Rgb(255, 255, 255),
+        }
+    }
+}
+
+#[allow(clippy::indexing_slicing, clippy::integer_arithmetic)]
+pub fn signs(changes: &[Change], new_len: usize) -> Vec<Sign> {
+    let mut signs = vec![Sign::None; new_len];
+    let mut new_index = 0;
+    let mut index = 0;
+    while let Some(change) = changes.get(index) {
+        if *change == Change::Unchanged {
+            new_index += 1;
+            index += 1;
+            continue;
+        }
+        let run_start = new_index;
+        let mut removed = 0_usize;
+        while let Some(change) = changes.get(index) {
+            match change {
+                Change::Unchanged => break,
+                Change::Added => new_index += 1,
+                Change::Removed => removed += 1,
+            }
+            index += 1;
+        }
+        for (offset, sign) in signs[run_start..new_index].iter_mut().enumerate() {
+            *sign = if offset < removed {
+                Sign::Modified
+            } else {
+                Sign::Added
+            };
+        }
+        if removed > new_index.saturating_sub(run_start) {
+            let marker = new_index.min(new_len.saturating_sub(1));
+            if let Some(sign) = signs.get_mut(marker) {
+                if *sign == Sign::None {
+                    *sign = Sign::Removed;
+                }
+            }
+        }
+    }
+    signs
+}
@@ -1,9 +1,180 @@
-use crate::{FileType, Position, Row, SearchDirection};
+use crate::brackets;
+use crate::config::{self, Settings};
+use crate::diff::{self, Sign, Summary};
+use crate::filter;
+use crate::lazy::{LazyLines, LAZY_THRESHOLD};
+use crate::{Continuation, Dictionary, FileType, Position, Row, SearchDirection};
 use std::{
+    borrow::Cow,
     fs,
     io::{self, Write},
-    path::Path,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    fn detect(content: &str) -> Self {
+        match content.find('\n') {
+            Some(index) if content[..index].ends_with('\r') => Self::CrLf,
+            _ => Self::Lf,
+        }
+    }
+
+    // Some lines end in CRLF and others in a bare LF.
+    pub fn is_mixed(content: &str) -> bool {
+        let (mut crlf, mut lf) = (false, false);
+        for line in content.split_inclusive('\n') {
+            if line.ends_with("\r\n") {
+                crlf = true;
+            } else if line.ends_with('\n') {
+                lf = true;
+            }
+        }
+        crlf && lf
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::CrLf => "CRLF",
+        }
+    }
+}
+
+// What the file is read and written as.
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Latin1,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().replace(['-', '_', ' '], "").as_str() {
+            "utf8" => Some(Self::Utf8),
+            "latin1" | "iso88591" => Some(Self::Latin1),
+            "utf16le" | "utf16" => Some(Self::Utf16Le),
+            "utf16be" => Some(Self::Utf16Be),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "UTF-8",
+            Self::Latin1 => "Latin-1",
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+        }
+    }
+
+    // UTF-16 is told apart by its byte order mark, which is left out of the
+    // text, and whatever isn't UTF-8 either is taken for Latin-1, unless it
+    // has bytes that are control characters there, which no text file has.
+    fn decode(bytes: Vec<u8>) -> Result<(String, Self), io::Error> {
+        let not_text = || io::Error::new(io::ErrorKind::InvalidData, "not text");
+        let (encoding, from_bytes): (Self, fn([u8; 2]) -> u16) = match bytes.get(..2) {
+            Some([0xff, 0xfe]) => (Self::Utf16Le, u16::from_le_bytes),
+            Some([0xfe, 0xff]) => (Self::Utf16Be, u16::from_be_bytes),
+            _ => {
+                let bytes = match String::from_utf8(bytes) {
+                    Ok(content) => return Ok((content, Self::Utf8)),
+                    Err(err) => err.into_bytes(),
+                };
+                let is_control =
+                    |byte: &u8| matches!(byte, 0x00..=0x08 | 0x0e..=0x1f | 0x7f..=0x9f);
+                if bytes.iter().any(is_control) {
+                    return Err(not_text());
+                }
+                return Ok((bytes.into_iter().map(char::from).collect(), Self::Latin1));
+            }
+        };
+        let units = bytes.get(2..).unwrap_or_default().chunks_exact(2);
+        if !units.remainder().is_empty() {
+            return Err(not_text());
+        }
+        let units: Vec<u16> = units
+            .map(|pair| from_bytes(pair.try_into().unwrap_or_default()))
+            .collect();
+        String::from_utf16(&units)
+            .map(|content| (content, encoding))
+            .map_err(|_| not_text())
+    }
+
+    // UTF-16 always gets a byte order mark, which a leading U+FEFF in the
+    // text turns into rather than doubling it. Latin-1 has none.
+    fn encode(self, content: &str) -> Result<Vec<u8>, io::Error> {
+        let text = content.strip_prefix('\u{feff}').unwrap_or(content);
+        match self {
+            Self::Utf8 => Ok(content.as_bytes().to_vec()),
+            Self::Latin1 => text
+                .chars()
+                .map(|c| u8::try_from(u32::from(c)).map_err(|_| c))
+                .collect::<Result<Vec<u8>, char>>()
+                .map_err(|c| {
+                    let line = text
+                        .split(c)
+                        .next()
+                        .map_or(0, |before| before.matches('\n').count());
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "'{c}' on line {} cannot be saved as Latin-1",
+                            line.saturating_add(1)
+                        ),
+                    )
+                }),
+            Self::Utf16Le => Ok(['\u{feff}']
+                .into_iter()
+                .chain(text.chars())
+                .collect::<String>()
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes)
+                .collect()),
+            Self::Utf16Be => Ok(['\u{feff}']
+                .into_iter()
+                .chain(text.chars())
+                .collect::<String>()
+                .encode_utf16()
+                .flat_map(u16::to_be_bytes)
+                .collect()),
+        }
+    }
+}
+
+const UNDO_LIMIT: usize = 1000;
+// Typing after a pause this long starts a new undo step.
+const UNDO_GROUP_GAP: Duration = Duration::from_secs(1);
+
+// Replaces `count` rows from `y` with `rows` and puts the cursor at `cursor`.
+// Applying an edit hands back its inverse.
+#[derive(Debug)]
+struct Edit {
+    y: usize,
+    count: usize,
+    rows: Vec<Row>,
+    cursor: Position,
+    inverse_cursor: Position,
+}
 
 #[derive(Default, Debug)]
 pub struct Document {
@@ -11,10 +182,36 @@ pub struct Document {
     pub file_name: Option<String>,
     dirty: bool,
     file_type: FileType,
+    settings: Settings,
+    // The file name the file type and settings were worked out for.
+    detected_for: Option<String>,
+    baseline: Vec<String>,
+    signs: Vec<Sign>,
+    signs_stale: bool,
+    brackets_stale: bool,
+    lazy: Option<LazyLines>,
+    missing_final_newline: bool,
+    line_ending: LineEnding,
+    disk_size: u64,
+    mixed_line_endings: bool,
+    mixed_indent: bool,
+    preserve_line_endings: bool,
+    encoding: Encoding,
+    revision: u64,
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    typing: Option<Instant>,
 }
 
 impl Document {
     pub fn open(path: &str) -> Result<Self, std::io::Error> {
+        Self::open_with_progress(path, &mut |_| {})
+    }
+
+    pub fn open_with_progress(
+        path: &str,
+        progress: &mut impl FnMut(usize),
+    ) -> Result<Self, std::io::Error> {
         let path = Path::new(path);
         let file_name = if let Some(s) = path.to_str() {
             Some(s.to_string())
@@ -22,54 +219,245 @@ impl Document {
             None
         };
 
-        let content = fs::read_to_string(path)?;
-        let file_type = FileType::from(path.file_name().unwrap().to_str().unwrap());
-        let mut rows = Vec::new();
-        for value in content.lines() {
-            rows.push(Row::from(value));
+        if fs::metadata(path)?.len() >= LAZY_THRESHOLD {
+            return Self::open_lazy(path, file_name, progress);
         }
-        let rows: Vec<Row> = content.lines().map(Row::from).collect();
+        let bytes = fs::read(path)?;
+        let disk_size = bytes.len() as u64;
+        let (content, encoding) = Encoding::decode(bytes)?;
+        let file_type = FileType::detect(
+            path.file_name().unwrap().to_str().unwrap(),
+            content.lines().next(),
+        );
+        // Every line is one row and a final newline does not start another,
+        // so "" has no rows and "\n" one empty row. The cursor can also rest
+        // on the line after the last row, where typing appends a new row.
+        let rows = split_rows(&content);
+        let baseline = rows.iter().map(|row| row.as_str().to_string()).collect();
+        progress(rows.len());
+        let mut settings = Settings::for_file(path);
+        settings.detect_indent(&rows);
+        let mixed_indent = config::has_mixed_indent(&rows);
         Ok(Self {
             rows,
+            detected_for: file_name.clone(),
             file_name,
             dirty: false,
             file_type,
+            settings,
+            baseline,
+            signs: Vec::new(),
+            signs_stale: true,
+            brackets_stale: true,
+            lazy: None,
+            missing_final_newline: !content.is_empty() && !content.ends_with('\n'),
+            line_ending: LineEnding::detect(&content),
+            disk_size,
+            mixed_line_endings: LineEnding::is_mixed(&content),
+            mixed_indent,
+            preserve_line_endings: false,
+            encoding,
+            revision: 0,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            typing: None,
         })
     }
 
+    // Always reads through the lazy window, whatever the file's size, so a
+    // followed log can keep growing without the whole of it in memory.
+    pub fn open_read_only(path: &str) -> Result<Self, io::Error> {
+        let file_name = Some(path.to_string());
+        Self::open_lazy(Path::new(path), file_name, &mut |_| {})
+    }
+
+    fn open_lazy(
+        path: &Path,
+        file_name: Option<String>,
+        progress: &mut impl FnMut(usize),
+    ) -> Result<Self, io::Error> {
+        let lazy = LazyLines::open(path, progress)?;
+        let file_type = FileType::detect(
+            path.file_name().unwrap().to_str().unwrap(),
+            lazy.read_row(0).as_ref().map(Row::as_str),
+        );
+        Ok(Self {
+            detected_for: file_name.clone(),
+            file_name,
+            file_type,
+            settings: Settings::for_file(path),
+            lazy: Some(lazy),
+            disk_size: fs::metadata(path)?.len(),
+            ..Self::default()
+        })
+    }
+
+    pub fn refresh(&mut self) -> Result<bool, io::Error> {
+        let grew = match &mut self.lazy {
+            Some(lazy) => lazy.refresh()?,
+            None => false,
+        };
+        if grew {
+            self.revision = self.revision.wrapping_add(1);
+            if let Some(file_name) = &self.file_name {
+                self.disk_size = fs::metadata(file_name)?.len();
+            }
+        }
+        Ok(grew)
+    }
+
+    pub fn load_rows(&mut self, start: usize, end: usize) -> Result<(), io::Error> {
+        if let Some(lazy) = &mut self.lazy {
+            if !lazy.contains(start, end.min(lazy.len())) {
+                self.rows = lazy.load(start, end)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.lazy.is_some()
+    }
+
     pub fn row(&self, index: usize) -> Option<&Row> {
+        if let Some(lazy) = &self.lazy {
+            return index
+                .checked_sub(lazy.window_start())
+                .and_then(|index| self.rows.get(index));
+        }
         self.rows.get(index)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.rows.is_empty()
+        self.len() == 0
     }
 
     pub fn len(&self) -> usize {
+        if let Some(lazy) = &self.lazy {
+            return lazy.len();
+        }
         self.rows.len()
     }
 
-    pub fn insert(&mut self, at: &Position, c: char) {
-        if at.y > self.len() {
-            return;
-        }
-        self.dirty = true;
+    pub fn insert(&mut self, at: &Position, c: char) -> Result<(), String> {
+        self.check_insert(at)?;
+        self.changed();
+        let old_len = self.rows.len();
         if c == '\n' {
+            let old_rows = self.rows.get(at.y).cloned().into_iter().collect();
             self.insert_newline(at);
-        } else if at.y == self.rows.len() {
-            let mut row = Row::default();
-            row.insert(0, c);
-            self.rows.push(row);
+            let end = Position {
+                x: 0,
+                y: at.y.saturating_add(1),
+            };
+            self.record(at.y, old_rows, old_len, *at, end);
         } else {
-            self.rows[at.y].insert(at.x, c);
+            let end = Position {
+                x: at.x.saturating_add(1),
+                y: at.y,
+            };
+            // Typing on from the last edit is undone along with it, so the
+            // row is only copied when a new edit starts.
+            let old_rows: Option<Vec<Row>> = if self.continue_typing(at, end) {
+                None
+            } else {
+                Some(self.rows.get(at.y).cloned().into_iter().collect())
+            };
+            if at.y == self.rows.len() {
+                let mut row = Row::default();
+                row.insert(0, c);
+                self.rows.push(row);
+            } else {
+                self.rows[at.y].insert(at.x, c);
+            }
+            if let Some(old_rows) = old_rows {
+                self.record(at.y, old_rows, old_len, *at, end);
+            }
+            self.typing = Some(Instant::now());
         }
+        self.unhighlight_rows(at.y);
+        Ok(())
     }
 
-    fn insert_newline(&mut self, at: &Position) {
-        if at.y > self.len() {
-            return;
+    // Replaces the grapheme at `at` with `c` as one edit, which groups with
+    // typing like an insert does.
+    pub fn overwrite(&mut self, at: &Position, c: char) -> Result<(), String> {
+        self.check_insert(at)?;
+        let len = self.rows.len();
+        if self.rows.get(at.y).map_or(true, |row| at.x >= row.len()) {
+            return self.insert(at, c);
         }
-        if at.y >= self.len() {
+        let end = Position {
+            x: at.x.saturating_add(1),
+            y: at.y,
+        };
+        let old_rows: Option<Vec<Row>> = if self.continue_typing(at, end) {
+            None
+        } else {
+            Some(self.rows.get(at.y).cloned().into_iter().collect())
+        };
+        if let Some(row) = self.rows.get_mut(at.y) {
+            row.delete(at.x);
+            row.insert(at.x, c);
+        }
+        self.changed();
+        if let Some(old_rows) = old_rows {
+            self.record(at.y, old_rows, len, *at, end);
+        }
+        self.typing = Some(Instant::now());
+        self.unhighlight_rows(at.y);
+        Ok(())
+    }
+
+    // Inserts `text` verbatim: only its first line joins the row at `at`,
+    // the others keep their own indentation. Returns the position right
+    // after the inserted text.
+    #[allow(clippy::indexing_slicing)]
+    pub fn insert_text(&mut self, at: &Position, text: &str) -> Result<Position, String> {
+        self.check_insert(at)?;
+        self.changed();
+        let old_len = self.rows.len();
+        let old_rows = self.rows.get(at.y).cloned().into_iter().collect();
+        if at.y == self.rows.len() {
+            self.rows.push(Row::default());
+        }
+        let tail = self.rows[at.y].split(at.x);
+        let mut end = *at;
+        for (index, line) in text.split('\n').enumerate() {
+            let line = Row::from(line.strip_suffix('\r').unwrap_or(line));
+            if index == 0 {
+                self.rows[end.y].append(&line);
+            } else {
+                end.y = end.y.saturating_add(1);
+                self.rows.insert(end.y, line);
+            }
+            end.x = self.rows[end.y].len();
+        }
+        self.rows[end.y].append(&tail);
+        self.record(at.y, old_rows, old_len, *at, end);
+        self.unhighlight_rows(at.y);
+        Ok(end)
+    }
+
+    // Positions may point at the end of a row or at the empty line after the
+    // last row, but no further.
+    fn check_insert(&self, at: &Position) -> Result<(), String> {
+        if self.is_read_only() {
+            return Err("File is opened read-only.".to_string());
+        }
+        let width = self.rows.get(at.y).map_or(0, Row::len);
+        if at.y > self.rows.len() || at.x > width {
+            return Err(format!(
+                "Cannot insert at line {}, column {}: past the end of the document.",
+                at.y.saturating_add(1),
+                at.x.saturating_add(1)
+            ));
+        }
+        Ok(())
+    }
+
+    fn insert_newline(&mut self, at: &Position) {
+        if at.y == self.rows.len() {
             self.rows.push(Row::default());
             return;
         }
@@ -78,50 +466,509 @@ impl Document {
         self.rows.insert(at.y + 1, new_row);
     }
 
+    pub fn insert_line_below(&mut self, y: usize) -> Option<Position> {
+        self.insert_line(y.saturating_add(1), y)
+    }
+
+    pub fn insert_line_above(&mut self, y: usize) -> Option<Position> {
+        self.insert_line(y, y)
+    }
+
+    // Opens a new row carrying over the indentation of row `indent_from`,
+    // leaving the existing rows untouched. Returns where the cursor belongs.
+    fn insert_line(&mut self, y: usize, indent_from: usize) -> Option<Position> {
+        if self.is_read_only() {
+            return None;
+        }
+        let y = y.min(self.len());
+        let indent: String = self.rows.get(indent_from).map_or_else(String::new, |row| {
+            row.as_str()
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect()
+        });
+        self.changed();
+        let old_len = self.rows.len();
+        let row = Row::from(indent.as_str());
+        let x = row.len();
+        self.rows.insert(y, row);
+        let from = Position {
+            x: 0,
+            y: indent_from,
+        };
+        self.record(y, Vec::new(), old_len, from, Position { x, y });
+        self.unhighlight_rows(y);
+        Some(Position { x, y })
+    }
+
     #[allow(clippy::integer_arithmetic, clippy::indexing_slicing)]
     pub fn delete(&mut self, at: &Position) {
         let len = self.len();
-        if at.y >= len {
+        if at.y >= len || self.is_read_only() {
             return;
         }
-
-        self.dirty = true;
-        if at.x == self.rows[at.y].len() && at.y + 1 < len {
+        // Delete at the end of the last row removes nothing, so it isn't an
+        // edit either.
+        let joins = at.x == self.rows[at.y].len() && at.y + 1 < len;
+        if !joins && at.x >= self.rows[at.y].len() {
+            return;
+        }
+        self.changed();
+        if joins {
+            let old_rows = self.rows[at.y..at.y + 2].to_vec();
             let next_row = self.rows.remove(at.y + 1);
             self.rows[at.y].append(&next_row);
+            self.record(at.y, old_rows, len, *at, *at);
         } else {
+            let old_rows = vec![self.rows[at.y].clone()];
             self.rows[at.y].delete(at.x);
+            self.record(at.y, old_rows, len, *at, *at);
         }
         self.unhighlight_rows(at.y);
     }
 
+    // The text from `start` up to `end`, with rows joined by `\n`.
+    pub fn text_range(&self, start: &Position, end: &Position) -> String {
+        let mut text = String::new();
+        let end_y = end.y.min(self.rows.len().saturating_sub(1));
+        for (y, row) in self
+            .rows
+            .iter()
+            .enumerate()
+            .take(end_y.saturating_add(1))
+            .skip(start.y)
+        {
+            let from = if y == start.y { start.x } else { 0 };
+            let to = if y == end.y { end.x } else { row.len() };
+            text.extend(
+                row.as_str()
+                    .graphemes(true)
+                    .skip(from)
+                    .take(to.saturating_sub(from)),
+            );
+            if y < end_y {
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    // Removes everything from `start` up to `end` as a single edit and returns
+    // it, with the rows it spanned joined by `\n`.
+    #[allow(clippy::indexing_slicing)]
+    pub fn delete_range(&mut self, start: &Position, end: &Position) -> String {
+        let len = self.rows.len();
+        if self.is_read_only() || start.y >= len || (end.y, end.x) <= (start.y, start.x) {
+            return String::new();
+        }
+        let (end_y, end_x) = if end.y < len {
+            (end.y, end.x.min(self.rows[end.y].len()))
+        } else {
+            (
+                len.saturating_sub(1),
+                self.rows[len.saturating_sub(1)].len(),
+            )
+        };
+        let start_x = start.x.min(self.rows[start.y].len());
+        if (end_y, end_x) <= (start.y, start_x) {
+            return String::new();
+        }
+        let removed = self.text_range(
+            &Position {
+                x: start_x,
+                y: start.y,
+            },
+            &Position { x: end_x, y: end_y },
+        );
+        self.changed();
+        let old_rows = self.rows[start.y..=end_y].to_vec();
+        let mut joined = self.rows[start.y].clone();
+        joined.split(start_x);
+        let tail = self.rows[end_y].clone().split(end_x);
+        joined.append(&tail);
+        joined.set_ending(self.rows[end_y].ending());
+        self.rows.splice(start.y..=end_y, [joined]);
+        self.record(start.y, old_rows, len, *start, *start);
+        self.unhighlight_rows(start.y);
+        removed
+    }
+
+    // The text as it would be saved, so that it survives a trip through
+    // replace_content: kept line endings and a missing final newline too.
+    pub fn content(&self) -> String {
+        let last = self.rows.len().saturating_sub(1);
+        let mut content = String::new();
+        for (index, row) in self.rows.iter().enumerate() {
+            content.push_str(row.as_str());
+            if index == last && self.missing_final_newline {
+                break;
+            }
+            match row.ending() {
+                _ if !self.preserve_line_endings => content.push('\n'),
+                Some(ending) => content.push_str(ending.as_str()),
+                None => content.push_str(self.line_ending.as_str()),
+            }
+        }
+        content
+    }
+
+    pub fn replace_content(&mut self, content: &str) {
+        if self.is_read_only() {
+            return;
+        }
+        let old_rows = std::mem::replace(&mut self.rows, split_rows(content));
+        self.record_all(old_rows);
+        self.missing_final_newline = !content.is_empty() && !content.ends_with('\n');
+        self.mixed_indent = config::has_mixed_indent(&self.rows);
+        self.changed();
+    }
+
+    pub fn replace_all(
+        &mut self,
+        query: &str,
+        replacement: &str,
+        case_sensitive: bool,
+        range: Option<&(Position, Position)>,
+    ) -> usize {
+        if self.is_read_only() {
+            return 0;
+        }
+        if let Some(range) = range {
+            return self.replace_within(query, replacement, case_sensitive, range);
+        }
+        let old_rows = self.rows.clone();
+        let count = self
+            .rows
+            .iter_mut()
+            .map(|row| row.replace_all(query, replacement, case_sensitive))
+            .fold(0, usize::saturating_add);
+        if count == 0 {
+            return 0;
+        }
+        if replacement.contains('\n') {
+            // New lines split off like typed ones; the last piece keeps the
+            // row's own ending.
+            self.rows = self
+                .rows
+                .iter()
+                .flat_map(|row| {
+                    let mut lines: Vec<Row> = row
+                        .as_str()
+                        .split('\n')
+                        .map(|line| Row::from(line.strip_suffix('\r').unwrap_or(line)))
+                        .collect();
+                    if let Some(last) = lines.last_mut() {
+                        last.set_ending(row.ending());
+                    }
+                    lines
+                })
+                .collect();
+        }
+        self.record_all(old_rows);
+        self.changed();
+        self.unhighlight_all();
+        count
+    }
+
+    // Replaces the matches lying wholly inside `range` one at a time, its
+    // end moving along as the replacements change the last row.
+    fn replace_within(
+        &mut self,
+        query: &str,
+        replacement: &str,
+        case_sensitive: bool,
+        range: &(Position, Position),
+    ) -> usize {
+        let (mut at, mut end) = *range;
+        let undo_depth = self.undo_depth();
+        let mut count: usize = 0;
+        while let Some(found) = self.find(
+            query,
+            &at,
+            SearchDirection::Forward,
+            case_sensitive,
+            Some(&(at, end)),
+        ) {
+            let len = self
+                .row(found.y)
+                .and_then(|row| row.match_len(found.x, query, case_sensitive))
+                .unwrap_or(0);
+            let match_end = Position {
+                x: found.x.saturating_add(len),
+                y: found.y,
+            };
+            self.delete_range(&found, &match_end);
+            at = match self.insert_text(&found, replacement) {
+                Ok(inserted_end) => inserted_end,
+                Err(_) => break,
+            };
+            if end.y == found.y {
+                end.x = end.x.saturating_sub(match_end.x).saturating_add(at.x);
+                end.y = at.y;
+            }
+            count = count.saturating_add(1);
+        }
+        self.join_edits(undo_depth);
+        count
+    }
+
+    // Swaps the `old_len` graphemes at `at` for `new`, which must not span
+    // rows. Returns the position right after the replacement.
+    #[allow(clippy::indexing_slicing)]
+    pub fn replace_at(&mut self, at: &Position, old_len: usize, new: &str) -> Option<Position> {
+        if self.is_read_only() || at.y >= self.rows.len() || at.x > self.rows[at.y].len() {
+            return None;
+        }
+        self.changed();
+        let len = self.rows.len();
+        let old_rows = vec![self.rows[at.y].clone()];
+        self.rows[at.y].replace(at.x, old_len, new);
+        let end = Position {
+            x: at.x.saturating_add(new.graphemes(true).count()),
+            y: at.y,
+        };
+        self.record(at.y, old_rows, len, *at, end);
+        self.unhighlight_rows(at.y);
+        Some(end)
+    }
+
+    // Converts the indentation of every row, leaving whitespace after the
+    // first non-blank character alone. Returns the number of rows changed.
+    pub fn retab(&mut self, to_spaces: bool, width: usize) -> usize {
+        if self.is_read_only() {
+            return 0;
+        }
+        let old_rows = self.rows.clone();
+        let count = self
+            .rows
+            .iter_mut()
+            .map(|row| row.retab(to_spaces, width))
+            .filter(|changed| *changed)
+            .count();
+        if count == 0 {
+            return 0;
+        }
+        self.record_all(old_rows);
+        self.settings.detect_indent(&self.rows);
+        self.mixed_indent = config::has_mixed_indent(&self.rows);
+        self.changed();
+        self.unhighlight_all();
+        count
+    }
+
+    pub fn format(&mut self, command: &str) -> Result<(), String> {
+        let content = self.content();
+        let formatted = filter::pipe(command, &content)?;
+        if formatted != content {
+            self.replace_content(&formatted);
+        }
+        Ok(())
+    }
+
     pub fn save(&mut self) -> Result<(), io::Error> {
-        if let Some(file_name) = &self.file_name {
-            let mut file = fs::File::create(file_name)?;
-            self.file_type = FileType::from(file_name);
-            for row in &mut self.rows {
-                file.write_all(row.as_bytes())?;
-                file.write_all(b"\n")?;
+        if self.is_read_only() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "document is opened read-only",
+            ));
+        }
+        if let Some(file_name) = self.file_name.clone() {
+            // Only a new name can change what the file is, so a file type set
+            // since, for example from the config, stays.
+            if self.detected_for.as_deref() != Some(file_name.as_str()) {
+                self.file_type = FileType::detect(&file_name, self.rows.first().map(Row::as_str));
+                self.settings = Settings::for_file(Path::new(&file_name));
+                self.settings.detect_indent(&self.rows);
+                self.detected_for = Some(file_name.clone());
+            }
+            if self.settings.trim_trailing_whitespace == Some(true) {
+                self.trim_trailing_whitespace();
             }
+            let insert_final_newline = self.write(&file_name, &self.settings)?;
             self.dirty = false;
+            self.missing_final_newline = !insert_final_newline;
+            self.disk_size = fs::metadata(&file_name)?.len();
+            self.mixed_line_endings = self.preserve_line_endings && self.mixed_line_endings;
+            self.mixed_indent = config::has_mixed_indent(&self.rows);
+            self.baseline = self
+                .rows
+                .iter()
+                .map(|row| row.as_str().to_string())
+                .collect();
+            self.signs_stale = true;
+            self.brackets_stale = true;
+        }
+        Ok(())
+    }
+
+    // Trimming on save is an edit like any other, so undo brings the
+    // whitespace back rather than older text.
+    fn trim_trailing_whitespace(&mut self) {
+        if self
+            .rows
+            .iter()
+            .all(|row| row.as_str().trim_end().len() == row.as_str().len())
+        {
+            return;
+        }
+        let old_rows = self.rows.clone();
+        for row in &mut self.rows {
+            row.trim_end();
+        }
+        self.record_all(old_rows);
+        self.changed();
+        self.unhighlight_all();
+    }
+
+    pub fn write_copy(&self, path: &str) -> Result<(), io::Error> {
+        if self.is_read_only() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "document is opened read-only",
+            ));
         }
+        self.write(path, &Settings::for_file(Path::new(path)))?;
         Ok(())
     }
 
-    pub fn find(&self, query: &str, at: &Position, direction: SearchDirection) -> Option<Position> {
-        if at.y >= self.rows.len() {
+    fn write(&self, path: &str, settings: &Settings) -> Result<bool, io::Error> {
+        let trim_trailing_whitespace = settings.trim_trailing_whitespace == Some(true);
+        let insert_final_newline = settings
+            .insert_final_newline
+            .unwrap_or(!self.missing_final_newline);
+        let last = self.rows.len().saturating_sub(1);
+        let mut content = String::new();
+        for (index, row) in self.rows.iter().enumerate() {
+            let line = if trim_trailing_whitespace {
+                row.as_str().trim_end()
+            } else {
+                row.as_str()
+            };
+            content.push_str(line);
+            if index < last || insert_final_newline {
+                let ending = match row.ending() {
+                    Some(ending) if self.preserve_line_endings => ending,
+                    _ => self.line_ending,
+                };
+                content.push_str(ending.as_str());
+            }
+        }
+        // Encode before touching the file, so text the encoding can't hold
+        // leaves it as it was.
+        let bytes = self.encoding.encode(&content)?;
+        replace_file(path, &bytes)?;
+        Ok(insert_final_newline)
+    }
+
+    pub fn diff_with_disk(&self) -> Result<Summary, io::Error> {
+        if self.is_read_only() {
+            return Ok(Summary::default());
+        }
+        let disk_content = if let Some(file_name) = &self.file_name {
+            Encoding::decode(fs::read(file_name)?)?.0
+        } else {
+            String::new()
+        };
+        let disk_lines: Vec<&str> = disk_content.lines().collect();
+        let buffer_lines: Vec<&str> = self.rows.iter().map(Row::as_str).collect();
+        Ok(Summary::from(&diff::diff(&disk_lines, &buffer_lines)))
+    }
+
+    pub fn refresh_signs(&mut self) {
+        if !self.signs_stale || self.is_read_only() {
+            return;
+        }
+        let buffer_lines: Vec<&str> = self.rows.iter().map(Row::as_str).collect();
+        let changes = diff::diff(&self.baseline, &buffer_lines);
+        self.signs = diff::signs(&changes, buffer_lines.len());
+        self.signs_stale = false;
+    }
+
+    pub fn refresh_brackets(&mut self) {
+        if !self.brackets_stale || self.is_read_only() {
+            return;
+        }
+        let opts = self.file_type.highlighting_options();
+        for row in &mut self.rows {
+            row.unmatched_brackets.clear();
+        }
+        if opts.brackets() {
+            for position in brackets::unmatched(&self.rows, opts) {
+                if let Some(row) = self.rows.get_mut(position.y) {
+                    row.unmatched_brackets.push(position.x);
+                }
+            }
+        }
+        self.brackets_stale = false;
+    }
+
+    // Brackets in strings and comments are skipped, so they have no partner.
+    pub fn find_matching_bracket(&self, at: &Position) -> Option<Position> {
+        if self.lazy.is_some() {
+            return None;
+        }
+        brackets::partner(&self.rows, self.file_type.highlighting_options(), at)
+    }
+
+    pub fn sign(&self, index: usize) -> Sign {
+        self.signs.get(index).copied().unwrap_or(Sign::None)
+    }
+
+    pub fn find(
+        &self,
+        query: &str,
+        at: &Position,
+        direction: SearchDirection,
+        case_sensitive: bool,
+        range: Option<&(Position, Position)>,
+    ) -> Option<Position> {
+        let (start, end) = match range {
+            Some(range) => *range,
+            None => return self.find_from(query, at, direction, case_sensitive),
+        };
+        // Backwards, matches never end past where the search starts, and
+        // forwards they never start before it, so only the far side of the
+        // range needs checking.
+        let from = if (at.y, at.x) < (start.y, start.x) {
+            start
+        } else if (at.y, at.x) > (end.y, end.x) {
+            end
+        } else {
+            *at
+        };
+        let found = self.find_from(query, &from, direction, case_sensitive)?;
+        let inside = if direction == SearchDirection::Forward {
+            self.read_row(found.y)
+                .and_then(|row| row.match_len(found.x, query, case_sensitive))
+                .map_or(false, |len| {
+                    (found.y, found.x.saturating_add(len)) <= (end.y, end.x)
+                })
+        } else {
+            (found.y, found.x) >= (start.y, start.x)
+        };
+        inside.then_some(found)
+    }
+
+    fn find_from(
+        &self,
+        query: &str,
+        at: &Position,
+        direction: SearchDirection,
+        case_sensitive: bool,
+    ) -> Option<Position> {
+        if at.y >= self.len() {
             return None;
         }
         let mut position = at.clone();
         let (start, end) = if direction == SearchDirection::Forward {
-            (at.y, self.rows.len())
+            (at.y, self.len())
         } else {
             (0, at.y.saturating_add(1))
         };
 
         for _ in start..end {
-            if let Some(row) = self.rows.get(position.y) {
-                if let Some(x) = row.find(&query, position.x, direction) {
+            if let Some(row) = self.read_row(position.y) {
+                if let Some(x) = row.find(query, position.x, direction, case_sensitive) {
                     position.x = x;
                     return Some(position);
                 }
@@ -129,10 +976,8 @@ impl Document {
                     position.y = position.y.saturating_add(1);
                     position.x = 0;
                 } else {
-                    position = Position {
-                        x: self.rows[position.y].len(),
-                        y: position.y.saturating_sub(1),
-                    };
+                    position.y = position.y.saturating_sub(1);
+                    position.x = self.read_row(position.y).map_or(0, |row| row.len());
                 }
             } else {
                 return None;
@@ -141,13 +986,213 @@ impl Document {
         None
     }
 
+    pub fn find_all(&self, query: &str, limit: usize, case_sensitive: bool) -> Vec<Position> {
+        let mut matches = Vec::new();
+        if query.is_empty() {
+            return matches;
+        }
+        // Matches may overlap, the same as for search and its highlighting.
+        for y in 0..self.len() {
+            if let Some(row) = self.read_row(y) {
+                let mut x = 0;
+                while let Some(found) = row.find(query, x, SearchDirection::Forward, case_sensitive)
+                {
+                    if matches.len() >= limit {
+                        return matches;
+                    }
+                    matches.push(Position { x: found, y });
+                    x = found.saturating_add(1);
+                }
+            }
+        }
+        matches
+    }
+
+    pub fn read_row(&self, index: usize) -> Option<Cow<'_, Row>> {
+        if let Some(row) = self.row(index) {
+            return Some(Cow::Borrowed(row));
+        }
+        self.lazy
+            .as_ref()
+            .and_then(|lazy| lazy.read_row(index))
+            .map(Cow::Owned)
+    }
+
     pub fn is_dirty(&self) -> bool {
         self.dirty
     }
 
-    pub fn highlight(&mut self, word: &Option<String>, until: Option<usize>) {
-        let mut start_with_comment = false;
-        let until = if let Some(until) = until {
+    // Goes up with every change to the text, so something outside the editor
+    // can poll it and only re-render when it moves.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    // Reverts the last edit and returns where the cursor belongs.
+    pub fn undo(&mut self) -> Option<Position> {
+        if self.is_read_only() {
+            return None;
+        }
+        self.end_undo_group();
+        let edit = self.undo.pop()?;
+        let cursor = edit.cursor;
+        let inverse = self.apply(edit);
+        self.redo.push(inverse);
+        Some(cursor)
+    }
+
+    // Reapplies the last undone edit, as long as nothing was edited since.
+    pub fn redo(&mut self) -> Option<Position> {
+        if self.is_read_only() {
+            return None;
+        }
+        self.end_undo_group();
+        let edit = self.redo.pop()?;
+        let cursor = edit.cursor;
+        let inverse = self.apply(edit);
+        self.undo.push(inverse);
+        Some(cursor)
+    }
+
+    // How many edits there are to undo, to pass to join_edits later.
+    pub fn undo_depth(&self) -> usize {
+        self.undo.len()
+    }
+
+    // Folds the edits made since the history was `depth` edits deep into one,
+    // so that they are undone together the way replace all is.
+    pub fn join_edits(&mut self, depth: usize) {
+        if self.undo.len() <= depth.saturating_add(1) {
+            return;
+        }
+        let dirty = self.dirty;
+        let new_rows = self.rows.clone();
+        let edits = self.undo.split_off(depth);
+        // Undoing goes back to where the first edit started, and redoing to
+        // where the last one left off.
+        let cursors = edits
+            .first()
+            .zip(edits.last())
+            .map(|(first, last)| (first.cursor, last.inverse_cursor));
+        for edit in edits.into_iter().rev() {
+            self.apply(edit);
+        }
+        let old_rows = std::mem::replace(&mut self.rows, new_rows);
+        self.record_all(old_rows);
+        if let (Some(edit), Some((cursor, inverse_cursor))) = (self.undo.last_mut(), cursors) {
+            edit.cursor = cursor;
+            edit.inverse_cursor = inverse_cursor;
+        }
+        self.dirty = dirty;
+        self.mixed_indent = config::has_mixed_indent(&self.rows);
+        self.unhighlight_all();
+    }
+
+    // Characters typed one after another are undone together, until the
+    // cursor moves, anything else is edited, or typing pauses.
+    pub fn end_undo_group(&mut self) {
+        self.typing = None;
+    }
+
+    fn continue_typing(&mut self, at: &Position, end: Position) -> bool {
+        if !self
+            .typing
+            .map_or(false, |typed| typed.elapsed() < UNDO_GROUP_GAP)
+        {
+            return false;
+        }
+        match self.undo.last_mut() {
+            Some(edit) if edit.count == 1 && edit.inverse_cursor == *at => {
+                edit.inverse_cursor = end;
+                self.redo.clear();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Called after an edit turned `old_rows`, which started at row `y`, into
+    // whatever is there now. `old_len` is how many rows there were before.
+    fn record(
+        &mut self,
+        y: usize,
+        old_rows: Vec<Row>,
+        old_len: usize,
+        before: Position,
+        after: Position,
+    ) {
+        let count = old_rows
+            .len()
+            .saturating_add(self.rows.len())
+            .saturating_sub(old_len);
+        self.redo.clear();
+        self.typing = None;
+        if self.undo.len() >= UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.undo.push(Edit {
+            y,
+            count,
+            rows: old_rows,
+            cursor: before,
+            inverse_cursor: after,
+        });
+    }
+
+    // Edits that rewrite the whole document put the cursor on the first row
+    // they changed.
+    fn record_all(&mut self, old_rows: Vec<Row>) {
+        let y = old_rows
+            .iter()
+            .zip(&self.rows)
+            .position(|(old, new)| old.as_str() != new.as_str())
+            .unwrap_or_else(|| old_rows.len().min(self.rows.len()));
+        let cursor = Position { x: 0, y };
+        let old_len = old_rows.len();
+        self.record(0, old_rows, old_len, cursor, cursor);
+    }
+
+    fn apply(&mut self, edit: Edit) -> Edit {
+        let y = edit.y.min(self.rows.len());
+        let end = y.saturating_add(edit.count).min(self.rows.len());
+        let count = edit.rows.len();
+        let rows = self.rows.splice(y..end, edit.rows).collect();
+        self.changed();
+        // Back where the file was last saved, nothing needs saving.
+        self.dirty = self.rows.len() != self.baseline.len()
+            || self
+                .rows
+                .iter()
+                .zip(&self.baseline)
+                .any(|(row, line)| row.as_str() != line);
+        self.mixed_indent = config::has_mixed_indent(&self.rows);
+        self.unhighlight_rows(y);
+        Edit {
+            y,
+            count,
+            rows,
+            cursor: edit.inverse_cursor,
+            inverse_cursor: edit.cursor,
+        }
+    }
+
+    fn changed(&mut self) {
+        self.dirty = true;
+        self.signs_stale = true;
+        self.brackets_stale = true;
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    pub fn highlight(
+        &mut self,
+        word: &Option<String>,
+        case_sensitive: bool,
+        dictionary: Option<&Dictionary>,
+        until: Option<usize>,
+    ) {
+        let mut continuation = Continuation::default();
+        let window_start = self.lazy.as_ref().map_or(0, LazyLines::window_start);
+        let until = if let Some(until) = until.map(|until| until.saturating_sub(window_start)) {
             if until.saturating_add(1) < self.rows.len() {
                 until.saturating_add(1)
             } else {
@@ -157,23 +1202,176 @@ impl Document {
             self.rows.len()
         };
 
+        let dictionary = dictionary.filter(|_| self.file_type.highlighting_options().spell_check());
         for row in &mut self.rows[..until] {
-            start_with_comment = row.highlight(
-                &self.file_type.highlighting_options(),
+            continuation = row.highlight(
+                self.file_type.highlighter(),
                 word,
-                start_with_comment,
+                case_sensitive,
+                dictionary,
+                continuation,
             );
         }
     }
 
+    // The indentation and line comment marker a row starts with, and the
+    // space after the marker if there is one. Repeats of the marker's last
+    // character count too, so `///` carries over as a whole.
+    #[allow(clippy::integer_arithmetic)]
+    pub fn comment_prefix(&self, y: usize) -> Option<String> {
+        let opts = self.file_type.highlighting_options();
+        if !opts.comments() {
+            return None;
+        }
+        let line = self.row(y)?.as_str();
+        if y == 0 && line.starts_with("#!") {
+            return None;
+        }
+        let text = line.trim_start_matches([' ', '\t']);
+        let marker = opts
+            .comment_starts()
+            .iter()
+            .find(|marker| text.starts_with(marker.as_str()))?;
+        let rest = text[marker.len()..].trim_start_matches(marker.chars().last()?);
+        let space = usize::from(rest.starts_with(' '));
+        let end = line.len() - rest.len() + space;
+        Some(line[..end].to_string())
+    }
+
+    pub fn url_at(&self, at: &Position) -> Option<String> {
+        self.row(at.y)?.url_at(at.x)
+    }
+
+    pub fn quote_pair(&self, at: &Position) -> Option<(usize, usize)> {
+        self.row(at.y)?
+            .quote_pair(at.x, &self.file_type.highlighting_options())
+    }
+
+    pub fn unhighlight_all(&mut self) {
+        for row in &mut self.rows {
+            row.is_highlighted = false;
+        }
+    }
+
     fn unhighlight_rows(&mut self, start: usize) {
-        let start = start.saturating_add(1);
         for row in self.rows.iter_mut().skip(start) {
             row.is_highlighted = false;
         }
     }
 
+    pub fn disk_size(&self) -> u64 {
+        self.disk_size
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    // Writes every row back with the line ending it was read with, instead of
+    // the one most of the file uses. Rows added since then use the latter.
+    pub fn set_preserve_line_endings(&mut self, preserve: bool) {
+        self.preserve_line_endings = preserve;
+    }
+
+    pub fn mixed_line_endings(&self) -> bool {
+        self.mixed_line_endings
+    }
+
+    pub fn mixed_indent(&self) -> bool {
+        self.mixed_indent
+    }
+
+    // Returns the encoding it replaces.
+    pub fn set_encoding(&mut self, encoding: Encoding) -> Encoding {
+        std::mem::replace(&mut self.encoding, encoding)
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        if self.encoding != Encoding::Utf8 {
+            return self.encoding.name();
+        }
+        let has_bom = self
+            .row(0)
+            .map_or(false, |row| row.as_str().starts_with('\u{feff}'));
+        if has_bom {
+            "UTF-8 BOM"
+        } else {
+            "UTF-8"
+        }
+    }
+
     pub fn file_type(&self) -> String {
         self.file_type.name()
     }
+
+    pub fn set_file_type(&mut self, file_type: FileType) {
+        self.file_type = file_type;
+        self.unhighlight_all();
+        self.brackets_stale = true;
+    }
+
+    pub fn indent_unit(&self) -> String {
+        self.settings.indent_unit()
+    }
+
+    pub fn indent_size(&self) -> usize {
+        self.settings.indent_size.unwrap_or(4)
+    }
+}
+
+// Every line is one row that remembers its ending, and a final newline does
+// not start another row.
+// Writes a temporary file next to `path` and renames it over the old one,
+// so a failed or interrupted save leaves the old file whole. The new file
+// gets the old one's mode, and its owner and group where we may set them.
+fn replace_file(path: &str, bytes: &[u8]) -> Result<(), io::Error> {
+    // Renaming over a symlink would replace the link, not what it points to.
+    let target = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    let file_name = target
+        .file_name()
+        .map_or_else(Default::default, |name| name.to_string_lossy());
+    let temp = target.with_file_name(format!(".{file_name}.rtext-save"));
+    let metadata = fs::metadata(&target).ok();
+    let mut file = match fs::File::create(&temp) {
+        Ok(file) => file,
+        // Without write access to the directory, the file can still be
+        // rewritten in place.
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied && metadata.is_some() => {
+            return fs::File::create(&target)?.write_all(bytes);
+        }
+        Err(err) => return Err(err),
+    };
+    let written = (|| {
+        file.write_all(bytes)?;
+        if let Some(metadata) = &metadata {
+            file.set_permissions(metadata.permissions())?;
+            // Only root may give a file away, so a file someone else owns
+            // ends up ours.
+            let _ = std::os::unix::fs::fchown(&file, Some(metadata.uid()), Some(metadata.gid()));
+        }
+        file.sync_all()?;
+        fs::rename(&temp, &target)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written
+}
+
+fn split_rows(content: &str) -> Vec<Row> {
+    content
+        .split_inclusive('\n')
+        .map(|line| {
+            let (line, ending) = if let Some(line) = line.strip_suffix("\r\n") {
+                (line, Some(LineEnding::CrLf))
+            } else if let Some(line) = line.strip_suffix('\n') {
+                (line, Some(LineEnding::Lf))
+            } else {
+                (line, None)
+            };
+            let mut row = Row::from(line);
+            row.set_ending(ending);
+            row
+        })
+        .collect()
 }
@@ -1,14 +1,88 @@
-use crate::{Document, Row, Terminal};
+use crate::clipboard;
+use crate::config::Config;
+use crate::crash;
+use crate::document::Encoding;
+use crate::filter;
+use crate::hex::HexView;
+use crate::highlighting;
+use crate::recent::RecentFiles;
+use crate::swap;
+use crate::{CursorShape, Dictionary, Document, FileType, Row, Size, Terminal};
+use std::cmp::Ordering;
 use std::env;
-use std::io;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
 use std::time::{Duration, Instant};
 use termion::color;
 use termion::event::Key;
+use unicode_segmentation::UnicodeSegmentation;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const STATUS_BG_COLOR: color::Rgb = color::Rgb(239, 239, 239);
 const STATUS_FG_COLOR: color::Rgb = color::Rgb(63, 63, 63);
-const QUIT_TIMES: u8 = 3;
+const EDGE_MARKER_COLOR: color::Rgb = color::Rgb(110, 110, 110);
+const MINIMAP_COLOR: color::Rgb = color::Rgb(90, 90, 90);
+const MINIMAP_VIEWPORT_BG_COLOR: color::Rgb = color::Rgb(40, 40, 40);
+// Text columns summed up by one minimap column.
+const MINIMAP_SCALE: usize = 4;
+const MAX_MATCHES: usize = 1000;
+const GUTTER_WIDTH: usize = 2;
+const EDIT_LOCATIONS: usize = 8;
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+const KEYBINDINGS: &[(&str, &str)] = &[
+    ("Ctrl-S", "Save the file"),
+    ("Alt-w", "Write a copy to another file"),
+    ("Alt-E", "Save with another encoding"),
+    ("Ctrl-O", "Open a file in a new buffer"),
+    ("Alt-.", "Switch to the next buffer"),
+    ("Alt-,", "Switch to the previous buffer"),
+    ("Ctrl-W", "Close the buffer"),
+    ("Ctrl-Q", "Quit"),
+    ("Ctrl-Z", "Undo"),
+    ("Ctrl-Y", "Redo"),
+    ("Ctrl-F", "Find"),
+    ("Ctrl-G", "Go to a line"),
+    ("Alt-]", "Jump to the matching bracket"),
+    ("Alt-f", "List every match of a search"),
+    ("Ctrl-R", "Replace matches one by one"),
+    ("Alt-r", "Replace every match of a search"),
+    ("Ctrl-L", "Clear the search highlight"),
+    ("Alt-T", "Convert the indentation to spaces or tabs"),
+    (
+        "Alt-i",
+        "Expand the snippet named by the word before the cursor",
+    ),
+    ("Ctrl-D", "Show unsaved changes"),
+    ("Ctrl-E", "Filter the buffer through a command"),
+    ("Alt-e", "Jump back through recent edit locations"),
+    ("Alt-z", "Scroll the cursor line to the middle"),
+    ("Alt-t", "Scroll the cursor line to the top"),
+    ("Alt-b", "Scroll the cursor line to the bottom"),
+    ("Alt-s", "Toggle spell checking"),
+    ("Alt-o", "Open the companion file"),
+    ("Alt-u", "Open the URL under the cursor in a browser"),
+    ("Ctrl-K", "Cut to the end of the line"),
+    ("Ctrl-U", "Paste what was cut"),
+    ("Ctrl-C", "Copy the line"),
+    ("Ctrl-V", "Paste from the system clipboard"),
+    ("Tab", "Insert indentation"),
+    ("Insert", "Toggle overwriting characters"),
+    ("Arrows", "Move the cursor"),
+    ("Shift-Arrows", "Select text"),
+    ("PageUp/PageDown", "Move by a screen"),
+    ("Home/End", "Move to the start/end of the line"),
+    ("Alt-x", "Toggle the read-only hex view"),
+    ("Alt-c", "Toggle the cursor column crosshair"),
+    ("Alt-v", "Toggle the minimap"),
+    ("Ctrl-T", "Toggle following the end of the file (--follow)"),
+    ("Alt-m", "Show the full status message"),
+    ("Alt-n", "Open a new line below"),
+    ("Alt-N", "Open a new line above"),
+    ("F1", "Show this help"),
+];
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum SearchDirection {
@@ -16,7 +90,7 @@ pub enum SearchDirection {
     Backward,
 }
 
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
@@ -37,6 +111,15 @@ impl StatusMessage {
     }
 }
 
+// An open document other than the one shown, with the view it was left in.
+#[derive(Debug, Default)]
+struct Buffer {
+    document: Document,
+    cursor_position: Position,
+    offset: Position,
+    edit_locations: Vec<Position>,
+}
+
 #[derive(Debug)]
 pub struct Editor {
     should_quit: bool,
@@ -44,39 +127,203 @@ pub struct Editor {
     cursor_position: Position,
     document: Document,
     offset: Position,
+    // The other open documents. The shown one sits at `buffer_index` among
+    // them, after buffers[..buffer_index] and before the rest.
+    buffers: Vec<Buffer>,
+    buffer_index: usize,
     status_message: StatusMessage,
     quit_times: u8,
     highlighted_word: Option<String>,
+    previous_frame: Vec<String>,
+    // The text area the previous frame was drawn for.
+    frame_size: Size,
+    show_help: bool,
+    show_message: bool,
+    desired_x: Option<usize>,
+    recent_files: RecentFiles,
+    welcome_selection: Option<usize>,
+    prompt_cursor: Option<usize>,
+    overwrite: bool,
+    clipboard: String,
+    killing: bool,
+    selection_start: Option<Position>,
+    // The selection that search and replace are kept inside of.
+    search_range: Option<(Position, Position)>,
+    cursor_shape: Option<CursorShape>,
+    hex_view: Option<HexView>,
+    edit_locations: Vec<Position>,
+    edit_jump: Option<usize>,
+    last_swap: Instant,
+    // Set when started with `--follow`; false while following is paused.
+    follow: Option<bool>,
+    config: Config,
+    dictionary: Option<Dictionary>,
 }
 
 impl Editor {
     pub fn new() -> Self {
+        crash::install_hook();
         let args: Vec<String> = env::args().collect();
         let mut initial_status =
-            String::from("HELP: Ctrl-F = find | Ctrl-S = save | Ctrl-Q = quit");
-        let mut document = if let Some(file_name) = args.get(1) {
-            let doc = Document::open(file_name);
-            if let Ok(doc) = doc {
-                doc
+            String::from("HELP: Ctrl-F = find | Ctrl-S = save | Ctrl-Q = quit | F1 = help");
+        let mut hex_view = None;
+        let follow = args.get(1).map_or(false, |arg| arg == "--follow");
+        let mut args = args.iter().skip(if follow { 2 } else { 1 });
+        // `--` ends option parsing, so a file named `--help` can be opened.
+        let file_arg = match args.next() {
+            Some(arg) if arg == "--" => args.next(),
+            arg => arg,
+        };
+        let location = file_arg.map(|arg| {
+            if follow {
+                (arg.as_str(), None, None)
+            } else {
+                parse_location(arg)
+            }
+        });
+        let document = if let Some((file_name, _, _)) = location {
+            let doc = if follow {
+                Document::open_read_only(file_name)
             } else {
-                initial_status = format!("ERR: Could not open file '{}'", args[1]);
-                Document::default()
+                Document::open_with_progress(file_name, &mut |lines| {
+                    print!("{}Loading... {lines} lines\r", termion::clear::CurrentLine);
+                    let _ = io::stdout().flush();
+                })
+            };
+            match doc {
+                Ok(doc) => doc,
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                    hex_view = HexView::open(Path::new(file_name)).ok();
+                    initial_status = format!("'{file_name}' is not text, showing its bytes.");
+                    Document::default()
+                }
+                Err(_) => {
+                    initial_status = format!("ERR: Could not open file '{file_name}'");
+                    Document::default()
+                }
             }
         } else {
             Document::default()
         };
+        let mut builder = EditorBuilder::new()
+            .terminal(Terminal::new().expect("failed to initialize terminal"))
+            .document(document)
+            .config(Config::load())
+            .follow(follow);
+        if let Some((_, Some(line), column)) = location {
+            builder = builder.cursor(Position {
+                x: column.unwrap_or(1).saturating_sub(1),
+                y: line.saturating_sub(1),
+            });
+        }
+        let mut editor = builder.build();
+        if editor.status_message.text.is_empty() {
+            editor.status_message = StatusMessage::from(initial_status);
+        }
+        editor.hex_view = hex_view;
+        editor.recent_files = RecentFiles::load();
+        if let Some(file_name) = &editor.document.file_name {
+            editor.recent_files.add(file_name);
+        } else if editor.hex_view.is_none() && !editor.recent_files.existing().is_empty() {
+            editor.welcome_selection = Some(0);
+        }
+        editor.offer_recovery();
+        if editor.config.spell_check {
+            editor.dictionary = Dictionary::load(&editor.config.dictionary_path).ok();
+        }
+        editor
+    }
+
+    pub fn with_terminal(terminal: Terminal, document: Document) -> Self {
+        EditorBuilder::new()
+            .terminal(terminal)
+            .document(document)
+            .build()
+    }
+
+    fn from_parts(mut terminal: Terminal, document: Document, config: Config) -> Self {
+        terminal.set_bar_rows(config.bar_rows);
+        let frame_size = terminal.size();
         Self {
             should_quit: false,
-            terminal: Terminal::new().expect("failed to initialize terminal"),
+            terminal,
             cursor_position: Position::default(),
             document,
             offset: Position::default(),
-            status_message: StatusMessage::from(initial_status),
-            quit_times: QUIT_TIMES,
+            buffers: Vec::new(),
+            buffer_index: 0,
+            status_message: StatusMessage::from(String::new()),
+            quit_times: config.quit_times,
             highlighted_word: None,
+            previous_frame: Vec::new(),
+            frame_size,
+            show_help: false,
+            show_message: false,
+            desired_x: None,
+            recent_files: RecentFiles::default(),
+            welcome_selection: None,
+            prompt_cursor: None,
+            overwrite: false,
+            clipboard: String::new(),
+            killing: false,
+            selection_start: None,
+            search_range: None,
+            cursor_shape: None,
+            hex_view: None,
+            edit_locations: Vec::new(),
+            edit_jump: None,
+            last_swap: Instant::now(),
+            follow: None,
+            config,
+            dictionary: None,
         }
     }
 
+    pub fn usage() -> String {
+        let mut usage = [
+            &Self::version(),
+            "",
+            "Usage: rtext [OPTIONS] [--] [FILE[:LINE[:COLUMN]]]",
+            "       rtext --follow [--] FILE",
+            "",
+            "Options:",
+            "  -h, --help     Print this help and exit",
+            "  -V, --version  Print the version and exit",
+            "  --follow       Show FILE read-only and follow lines appended to it",
+            "",
+            "Keybindings:",
+            "",
+        ]
+        .join("\n");
+        let key_width = KEYBINDINGS
+            .iter()
+            .map(|(key, _)| key.len())
+            .max()
+            .unwrap_or(0);
+        for (key, description) in KEYBINDINGS {
+            usage.push_str(&format!("  {key:<key_width$}  {description}\n"));
+        }
+        usage
+    }
+
+    pub fn version() -> String {
+        format!("RText {VERSION}")
+    }
+
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    pub fn cursor(&self) -> Position {
+        self.cursor_position
+    }
+
+    pub fn set_cursor(&mut self, position: Position) {
+        self.cursor_position = position;
+        self.desired_x = None;
+        self.clamp_cursor();
+    }
+
     pub fn run(&mut self) {
         loop {
             if let Err(err) = self.refresh_screen() {
@@ -84,22 +331,111 @@ impl Editor {
             }
 
             if self.should_quit {
+                let documents = self.buffers.iter().map(|buffer| &buffer.document);
+                for document in documents.chain([&self.document]) {
+                    if let Some(file_name) = &document.file_name {
+                        swap::remove(file_name);
+                    }
+                }
                 break;
             }
+            self.write_swap();
+            crash::set_context(format!(
+                "file: {}, line {}, column {}",
+                self.document.file_name.as_deref().unwrap_or("[No Name]"),
+                self.cursor_position.y.saturating_add(1),
+                self.cursor_position.x.saturating_add(1)
+            ));
 
             if let Err(err) = self.process_keypress() {
+                if err.kind() == io::ErrorKind::UnexpectedEof {
+                    break;
+                }
                 die(err);
             }
         }
     }
 
     fn process_keypress(&mut self) -> Result<(), std::io::Error> {
-        let pressed_key = Terminal::read_key()?;
+        let pressed_key = if self.follow == Some(true) {
+            match self.terminal.read_key_timeout(FOLLOW_INTERVAL)? {
+                Some(key) => key,
+                None => {
+                    self.refresh_follow();
+                    return Ok(());
+                }
+            }
+        } else {
+            self.terminal.read_key()?
+        };
+        if self.show_help || self.show_message {
+            self.show_help = false;
+            self.show_message = false;
+            return Ok(());
+        }
+        if self.hex_view.is_some() && self.process_hex_key(pressed_key) {
+            return Ok(());
+        }
+        if let Some(selected) = self.welcome_selection {
+            if self.process_welcome_key(pressed_key, selected) {
+                return Ok(());
+            }
+        }
+        if !matches!(pressed_key, Key::Char(_)) {
+            self.document.end_undo_group();
+        }
+        let selecting = self.terminal.shift()
+            && matches!(
+                pressed_key,
+                Key::Up
+                    | Key::Down
+                    | Key::Left
+                    | Key::Right
+                    | Key::PageUp
+                    | Key::PageDown
+                    | Key::Home
+                    | Key::End
+            );
+        if selecting && self.selection_start.is_none() {
+            self.selection_start = Some(self.cursor_position);
+        }
+        // Typing replaces the selection, and deleting takes just the selection.
+        let deleted_selection = matches!(pressed_key, Key::Char(_) | Key::Delete | Key::Backspace)
+            && !self.document.is_read_only()
+            && self.delete_selection();
+        if !matches!(
+            pressed_key,
+            Key::Up | Key::Down | Key::PageUp | Key::PageDown
+        ) {
+            self.desired_x = None;
+        }
+        if pressed_key != Key::Alt('e') {
+            self.edit_jump = None;
+        }
         match pressed_key {
+            Key::F(1) => self.show_help = true,
+            Key::Alt('m') => {
+                if self.status_message.text.is_empty() {
+                    self.status_message = StatusMessage::from("No message to show.".to_string());
+                } else {
+                    self.show_message = true;
+                }
+            }
             Key::Ctrl('q') => {
-                if self.quit_times > 0 && self.document.is_dirty() {
+                let unsaved = self.unsaved_buffers();
+                if self.quit_times > 0 && !unsaved.is_empty() {
+                    let times = if self.quit_times == 1 {
+                        "time"
+                    } else {
+                        "times"
+                    };
+                    let what = match unsaved.len() {
+                        _ if self.buffers.is_empty() => "File has unsaved changes".to_string(),
+                        1 => format!("1 unsaved buffer ({})", unsaved.join(", ")),
+                        count => format!("{count} unsaved buffers ({})", unsaved.join(", ")),
+                    };
                     self.status_message = StatusMessage::from(format!(
-                        "WARNING! File has unsaved changes. Press Ctrl-Q {} more times to quit.",
+                        "WARNING! {what}. Press Ctrl-Q {} more {times} to quit.",
                         self.quit_times
                     ));
                     self.quit_times -= 1;
@@ -107,19 +443,72 @@ impl Editor {
                 }
                 self.should_quit = true;
             }
-            Key::Ctrl('s') => self.save(),
+            Key::Ctrl('s') => {
+                self.save();
+            }
+            Key::Ctrl('z') => self.undo(),
+            Key::Ctrl('y') => self.redo(),
+            Key::Alt('w') => self.write_copy(),
+            Key::Alt('E') => self.save_with_encoding(),
+            Key::Ctrl('o') => self.open_file(),
+            Key::Alt('.') => self.switch_buffer(true),
+            Key::Alt(',') => self.switch_buffer(false),
+            Key::Ctrl('w') => self.close_file(),
+            Key::Ctrl('t') => self.toggle_follow(),
             Key::Ctrl('f') => self.search(),
-            Key::Char(c) => {
-                self.document.insert(&self.cursor_position, c);
-                self.move_cursor(Key::Right);
+            Key::Ctrl('g') => self.go_to_line(),
+            Key::Alt(']') => self.jump_to_bracket(),
+            Key::Alt('f') => self.list_matches()?,
+            Key::Ctrl('r') => self.replace_one_by_one()?,
+            Key::Alt('r') => self.replace_all(),
+            Key::Ctrl('l') => self.clear_search_highlight(),
+            Key::Alt('T') => self.retab(),
+            Key::Alt('i') => self.expand_snippet(),
+            Key::Ctrl('d') => self.preview_diff(),
+            Key::Alt('s') => self.toggle_spell_check(),
+            Key::Alt('x') => self.toggle_hex_view(),
+            Key::Alt('c') => self.config.crosshair = !self.config.crosshair,
+            Key::Alt('v') => self.config.minimap = !self.config.minimap,
+            Key::Alt('o') => self.open_companion(),
+            Key::Alt('u') => self.open_url(),
+            Key::Alt('e') => self.jump_to_edit(),
+            Key::Ctrl('e') => self.filter_buffer(),
+            Key::Alt('z') => self.scroll_cursor_to(self.terminal.size().height as usize / 2),
+            Key::Alt('t') => self.scroll_cursor_to(0),
+            Key::Alt('b') => {
+                self.scroll_cursor_to((self.terminal.size().height as usize).saturating_sub(1));
             }
-            Key::Delete => self.document.delete(&self.cursor_position),
-            Key::Backspace => {
-                if self.cursor_position.x > 0 || self.cursor_position.y > 0 {
-                    self.move_cursor(Key::Left);
-                    self.document.delete(&self.cursor_position);
+            Key::Char(_) | Key::Delete | Key::Backspace | Key::Ctrl('k' | 'u' | 'v')
+                if self.document.is_read_only() =>
+            {
+                self.status_message = StatusMessage::from("File is opened read-only.".to_string());
+            }
+            Key::Char('\t') => {
+                for c in self.document.indent_unit().chars() {
+                    if !self.insert_at_cursor(c) {
+                        break;
+                    }
+                }
+            }
+            Key::Ctrl('k') => self.cut_to_end_of_line(),
+            Key::Ctrl('u') => self.paste(),
+            Key::Ctrl('c') => self.copy_line(),
+            Key::Ctrl('v') => self.paste_system(),
+            Key::Char(c) => self.insert_char(c),
+            Key::Insert => self.overwrite = !self.overwrite,
+            Key::Alt('n') => {
+                if let Some(position) = self.document.insert_line_below(self.cursor_position.y) {
+                    self.set_cursor(position);
+                }
+            }
+            Key::Alt('N') => {
+                if let Some(position) = self.document.insert_line_above(self.cursor_position.y) {
+                    self.set_cursor(position);
                 }
             }
+            Key::Delete | Key::Backspace if deleted_selection => (),
+            Key::Delete => self.document.delete(&self.cursor_position),
+            Key::Backspace => self.backspace(),
             Key::Up
             | Key::Down
             | Key::Left
@@ -130,19 +519,337 @@ impl Editor {
             | Key::Home => self.move_cursor(pressed_key),
             _ => (),
         };
+        if matches!(
+            pressed_key,
+            Key::Char(_)
+                | Key::Delete
+                | Key::Backspace
+                | Key::Alt('n' | 'N' | 'i')
+                | Key::Ctrl('z' | 'y' | 'k' | 'u' | 'v')
+        ) && !self.document.is_read_only()
+        {
+            self.record_edit();
+        }
+        self.killing = pressed_key == Key::Ctrl('k');
+        if !selecting {
+            self.selection_start = None;
+        }
         self.scroll();
-        if self.quit_times < QUIT_TIMES {
-            self.quit_times = QUIT_TIMES;
+        if self.quit_times < self.config.quit_times {
+            self.quit_times = self.config.quit_times;
             self.status_message = StatusMessage::from(String::new());
         }
         Ok(())
     }
 
+    fn process_hex_key(&mut self, key: Key) -> bool {
+        let height = self.terminal.size().height as usize;
+        if let Some(hex_view) = &mut self.hex_view {
+            let row = hex_view.row;
+            match key {
+                Key::Up => hex_view.move_to(row.saturating_sub(1), height),
+                Key::Down => hex_view.move_to(row.saturating_add(1), height),
+                Key::PageUp => hex_view.move_to(row.saturating_sub(height), height),
+                Key::PageDown => hex_view.move_to(row.saturating_add(height), height),
+                Key::Home => hex_view.move_to(0, height),
+                Key::End => hex_view.move_to(usize::MAX, height),
+                Key::Alt('x') | Key::Esc => self.hex_view = None,
+                Key::Ctrl('q') => return false,
+                _ => (),
+            }
+        }
+        true
+    }
+
+    fn process_welcome_key(&mut self, key: Key, selected: usize) -> bool {
+        let count = self.recent_files.existing().len();
+        let choice = match key {
+            Key::Up => {
+                self.welcome_selection = Some(selected.saturating_sub(1));
+                return true;
+            }
+            Key::Down => {
+                self.welcome_selection =
+                    Some(selected.saturating_add(1).min(count.saturating_sub(1)));
+                return true;
+            }
+            Key::Char('\n') => Some(selected),
+            // Plain digits are text; the buffer may well start with one.
+            Key::Alt(c) => c
+                .to_digit(10)
+                .and_then(|digit| (digit as usize).checked_sub(1))
+                .filter(|index| *index < count),
+            _ => None,
+        };
+        self.welcome_selection = None;
+        if let Some(path) = choice.and_then(|index| {
+            self.recent_files
+                .existing()
+                .get(index)
+                .map(|path| path.to_string())
+        }) {
+            self.open(&path);
+            return true;
+        }
+        key == Key::Esc
+    }
+
+    fn insert_char(&mut self, c: char) {
+        if c == '\n' && self.config.continue_comments && self.continue_comment() {
+            return;
+        }
+        if self.overwrite && c != '\n' && self.char_at(0).is_some() {
+            match self.document.overwrite(&self.cursor_position, c) {
+                Ok(()) => self.move_cursor(Key::Right),
+                Err(err) => self.status_message = StatusMessage::from(format!("ERR: {err}")),
+            }
+            return;
+        }
+        if self.config.auto_pairs && !self.document.is_read_only() {
+            // Typing a closer right before the same closer steps over it.
+            if matches!(c, ')' | ']' | '}' | '"') && self.char_at(0) == Some(c) {
+                self.move_cursor(Key::Right);
+                return;
+            }
+            if let Some(closer) = closing_pair(c) {
+                if self.insert_at_cursor(c) {
+                    if let Err(err) = self.document.insert(&self.cursor_position, closer) {
+                        self.status_message = StatusMessage::from(format!("ERR: {err}"));
+                    }
+                }
+                return;
+            }
+        }
+        self.insert_at_cursor(c);
+    }
+
+    // Enter after a line comment marker starts the next row with the same
+    // marker, unless the comment is empty, which ends it instead. Returns
+    // false when the cursor is not in a line comment.
+    fn continue_comment(&mut self) -> bool {
+        let Position { x, y } = self.cursor_position;
+        let prefix = match self.document.comment_prefix(y) {
+            Some(prefix) if prefix.trim_end().graphemes(true).count() <= x => prefix,
+            _ => return false,
+        };
+        let row_len = self.document.row(y).map_or(0, Row::len);
+        if self
+            .document
+            .row(y)
+            .map_or(false, |row| row.as_str().trim_end() == prefix.trim_end())
+        {
+            let indent = prefix.len().saturating_sub(prefix.trim_start().len());
+            for _ in indent..row_len {
+                self.document.delete(&Position { x: indent, y });
+            }
+            self.set_cursor(Position { x: indent, y });
+            return true;
+        }
+        if !self.insert_at_cursor('\n') {
+            return true;
+        }
+        match self.document.insert_text(&self.cursor_position, &prefix) {
+            Ok(end) => self.set_cursor(end),
+            Err(err) => self.status_message = StatusMessage::from(format!("ERR: {err}")),
+        }
+        true
+    }
+
+    // Inserts `c` and steps past it, or reports why the insert was refused.
+    fn insert_at_cursor(&mut self, c: char) -> bool {
+        match self.document.insert(&self.cursor_position, c) {
+            Ok(()) => {
+                self.move_cursor(Key::Right);
+                true
+            }
+            Err(err) => {
+                self.status_message = StatusMessage::from(format!("ERR: {err}"));
+                false
+            }
+        }
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor_position.x == 0 && self.cursor_position.y == 0 {
+            return;
+        }
+        let between_pair = self.config.auto_pairs
+            && self.char_at(0).is_some()
+            && self.char_at(1).and_then(closing_pair) == self.char_at(0);
+        self.move_cursor(Key::Left);
+        // Both halves of the pair go in one edit, so one undo brings them back.
+        if between_pair {
+            let Position { x, y } = self.cursor_position;
+            let end = Position {
+                x: x.saturating_add(2),
+                y,
+            };
+            self.document.delete_range(&self.cursor_position, &end);
+        } else {
+            self.document.delete(&self.cursor_position);
+        }
+    }
+
+    // Cuts the rest of the line, or the line break when nothing is left.
+    // Cutting again right away adds to what was cut, like Emacs' kill.
+    fn cut_to_end_of_line(&mut self) {
+        let Position { x, y } = self.cursor_position;
+        let width = match self.document.row(y) {
+            Some(row) => row.len(),
+            None => return,
+        };
+        let end = if x < width {
+            Position { x: width, y }
+        } else {
+            Position {
+                x: 0,
+                y: y.saturating_add(1),
+            }
+        };
+        let cut = self.document.delete_range(&self.cursor_position, &end);
+        if !self.killing {
+            self.clipboard.clear();
+        }
+        self.clipboard.push_str(&cut);
+    }
+
+    fn paste(&mut self) {
+        self.paste_text(&self.clipboard.clone());
+    }
+
+    fn paste_text(&mut self, text: &str) {
+        if text.is_empty() {
+            self.status_message = StatusMessage::from("Nothing to paste.".to_string());
+            return;
+        }
+        match self.document.insert_text(&self.cursor_position, text) {
+            Ok(end) => self.set_cursor(end),
+            Err(err) => self.status_message = StatusMessage::from(format!("ERR: {err}")),
+        }
+    }
+
+    // Copies the selection, or the line without one. The text is kept in the
+    // internal clipboard too, for Ctrl-U and for when there's no system
+    // clipboard to paste from.
+    fn copy_line(&mut self) {
+        let (line, what) = if let Some((start, end)) = self.selection() {
+            (self.document.text_range(&start, &end), "selection")
+        } else if let Some(row) = self.document.row(self.cursor_position.y) {
+            (format!("{}\n", row.as_str()), "line")
+        } else {
+            return;
+        };
+        let message = match clipboard::copy(&line) {
+            Ok(()) => format!("Copied the {what}."),
+            Err(err) => format!("Copied the {what}, but only within the editor: {err}."),
+        };
+        self.clipboard = line;
+        self.status_message = StatusMessage::from(message);
+    }
+
+    // Returns whether there was any selected text to delete.
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some((start, end)) if start != end => {
+                self.document.delete_range(&start, &end);
+                self.selection_start = None;
+                self.set_cursor(start);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Search and replace stay inside a selection made beforehand, which
+    // itself gives way as the cursor moves on to the matches, starting from
+    // the top of it.
+    fn take_search_range(&mut self) -> Option<(Position, Position)> {
+        self.search_range = self.selection();
+        self.selection_start = None;
+        if let Some((start, _)) = self.search_range {
+            self.cursor_position = start;
+        }
+        self.search_range
+    }
+
+    // From wherever the selection started to the cursor, in document order.
+    fn selection(&self) -> Option<(Position, Position)> {
+        let start = self.selection_start?;
+        let end = self.cursor_position;
+        if (start.y, start.x) <= (end.y, end.x) {
+            Some((start, end))
+        } else {
+            Some((end, start))
+        }
+    }
+
+    fn paste_system(&mut self) {
+        match clipboard::paste() {
+            Ok(text) => self.paste_text(&text),
+            Err(err) if self.clipboard.is_empty() => {
+                self.status_message = StatusMessage::from(format!("Nothing to paste: {err}."));
+            }
+            Err(err) => {
+                self.paste();
+                self.status_message =
+                    StatusMessage::from(format!("Pasted from within the editor: {err}."));
+            }
+        }
+    }
+
+    // Returns the character `back` graphemes before the cursor, where 0 is
+    // the one under the cursor.
+    fn char_at(&self, back: usize) -> Option<char> {
+        let x = self.cursor_position.x.checked_sub(back)?;
+        let row = self.document.row(self.cursor_position.y)?;
+        row.as_str().graphemes(true).nth(x)?.chars().next()
+    }
+
+    // Consecutive edits on the same line count as one location, so the ring
+    // holds distinct places rather than every keystroke.
+    fn record_edit(&mut self) {
+        let position = self.cursor_position;
+        if self.edit_locations.last().map(|last| last.y) == Some(position.y) {
+            self.edit_locations.pop();
+        }
+        self.edit_locations.push(position);
+        if self.edit_locations.len() > EDIT_LOCATIONS {
+            self.edit_locations.remove(0);
+        }
+    }
+
+    #[allow(clippy::integer_arithmetic)]
+    fn jump_to_edit(&mut self) {
+        let count = self.edit_locations.len();
+        if count == 0 {
+            self.status_message = StatusMessage::from("No edits yet.".to_string());
+            return;
+        }
+        let jump = self
+            .edit_jump
+            .map_or(0, |jump| jump.saturating_add(1) % count);
+        if let Some(position) = self
+            .edit_locations
+            .get(count.saturating_sub(jump).saturating_sub(1))
+        {
+            self.cursor_position = *position;
+        }
+        self.edit_jump = Some(jump);
+        self.clamp_cursor();
+        self.status_message = StatusMessage::from(format!(
+            "Edit location {} of {count}",
+            jump.saturating_add(1)
+        ));
+    }
+
+    // Keeps the cursor inside the viewport. Horizontally the view only moves
+    // once the cursor leaves it, so moving vertically through ragged lines
+    // keeps the same offset as long as the (desired) column stays visible.
     fn scroll(&mut self) {
         let Position { x, y } = self.cursor_position;
-        let width = self.terminal.size().width as usize;
-        let height = self.terminal.size().height as usize;
-        let mut offset = &mut self.offset;
+        let width = self.text_width();
+        let height = (self.terminal.size().height as usize).max(1);
+        let offset = &mut self.offset;
         if y < offset.y {
             offset.y = y;
         } else if y >= offset.y.saturating_add(height) {
@@ -152,20 +859,34 @@ impl Editor {
         if x < offset.x {
             offset.x = x;
         } else if x >= offset.x.saturating_add(width) {
-            offset.x = x.saturating_add(width).saturating_add(1);
+            offset.x = x.saturating_sub(width).saturating_add(1);
         }
     }
 
+    fn scroll_cursor_to(&mut self, screen_row: usize) {
+        let height = self.terminal.size().height as usize;
+        let screen_row = screen_row.min(height.saturating_sub(1));
+        self.offset.y = self.cursor_position.y.saturating_sub(screen_row);
+    }
+
     fn move_cursor(&mut self, key: Key) {
         let Position { x, y } = self.cursor_position;
+        let x = if matches!(key, Key::Up | Key::Down | Key::PageUp | Key::PageDown) {
+            *self.desired_x.get_or_insert(x)
+        } else {
+            x
+        };
         let height = self.document.len();
-        let terminal_height = self.terminal.size().height as usize;
-        let mut width = if let Some(row) = self.document.row(y) {
+        let page = self
+            .config
+            .page_scroll_lines
+            .unwrap_or(self.terminal.size().height as usize);
+        let width = if let Some(row) = self.document.row(y) {
             row.len()
         } else {
             0
         };
-        let Position { mut y, mut x } = self.cursor_position;
+        let (mut x, mut y) = (x, y);
         match key {
             Key::Up => y = y.saturating_sub(1),
             Key::Down if y < height => y = y.saturating_add(1),
@@ -189,16 +910,10 @@ impl Editor {
                     }
                 }
             }
-            Key::PageUp => {
-                y = if y > terminal_height {
-                    y.saturating_sub(terminal_height)
-                } else {
-                    0
-                }
-            }
+            Key::PageUp => y = if y > page { y.saturating_sub(page) } else { 0 },
             Key::PageDown => {
-                y = if y.saturating_add(terminal_height) < height {
-                    y.saturating_add(terminal_height)
+                y = if y.saturating_add(page) < height {
+                    y.saturating_add(page)
                 } else {
                     height
                 }
@@ -207,152 +922,615 @@ impl Editor {
             Key::End => x = width,
             _ => (),
         }
-        width = if let Some(row) = self.document.row(y) {
-            row.len()
-        } else {
-            0
-        };
-        if x > width {
-            x = width;
-        }
         self.cursor_position = Position { x, y };
+        self.clamp_cursor();
     }
 
     fn refresh_screen(&mut self) -> Result<(), std::io::Error> {
-        Terminal::cursor_hide();
-        Terminal::cursor_position(&Position::default());
+        self.terminal.cursor_hide();
+        self.terminal.update_size();
+        if self.terminal.size() != self.frame_size {
+            // Nothing on a resized screen is where the last frame left it.
+            self.frame_size = self.terminal.size();
+            self.previous_frame.clear();
+            self.terminal.clear_screen();
+            self.scroll();
+        }
         if self.should_quit {
-            Terminal::clear_screen();
-            println!("Goodbye.\r");
+            self.terminal.cursor_position(&Position::default());
+            self.terminal.clear_screen();
+            self.terminal.print_line("Goodbye.");
+            if self.cursor_shape.is_some() {
+                self.terminal.set_cursor_shape(CursorShape::Default);
+            }
+        } else if self.show_help {
+            let frame = self.render_help();
+            self.draw_frame(frame);
+            self.terminal.cursor_position(&Position::default());
+        } else if self.show_message {
+            let frame = self.render_message_view();
+            self.draw_frame(frame);
+            self.terminal.cursor_position(&Position::default());
+        } else if let Some(hex_view) = &self.hex_view {
+            let y = hex_view.row.saturating_sub(hex_view.offset);
+            let frame = self.render_hex(hex_view)?;
+            self.draw_frame(frame);
+            self.terminal.cursor_position(&Position { x: 0, y });
         } else {
-            self.document.highlight(
-                &self.highlighted_word,
+            self.document.load_rows(
+                self.offset.y,
+                self.offset
+                    .y
+                    .saturating_add(self.terminal.size().height as usize),
+            )?;
+            // The minimap colors rows anywhere in the file, not just on screen.
+            let until = if self.minimap_width() > 0 {
+                None
+            } else {
                 Some(
                     self.offset
                         .y
                         .saturating_add(self.terminal.size().height as usize),
-                ),
+                )
+            };
+            let case_sensitive = self
+                .highlighted_word
+                .as_ref()
+                .map_or(true, |word| self.config.case_sensitive(word));
+            self.document.highlight(
+                &self.highlighted_word,
+                case_sensitive,
+                self.dictionary.as_ref(),
+                until,
             );
-            self.draw_rows();
-            self.draw_status_bar();
-            self.draw_message_bar();
-            Terminal::cursor_position(&Position {
-                x: self.cursor_position.x.saturating_sub(self.offset.x),
-                y: self.cursor_position.y.saturating_sub(self.offset.y),
-            });
+            self.document.refresh_signs();
+            self.document.refresh_brackets();
+            let mut frame = self.render_rows();
+            self.push_bars(&mut frame, self.render_message_bar());
+            self.draw_frame(frame);
+            let column = self.document.row(self.cursor_position.y).map_or_else(
+                || self.cursor_position.x.saturating_sub(self.offset.x),
+                |row| row.columns(self.offset.x, self.cursor_position.x),
+            );
+            let size = self.terminal.size();
+            match self.prompt_cursor {
+                Some(column) if self.terminal.bar_rows() > 1 => {
+                    // The input wraps like any message; past the last row the
+                    // cursor waits at its end.
+                    let width = (size.width as usize).max(1);
+                    let last_line = (self.terminal.bar_rows() as usize).saturating_sub(2);
+                    #[allow(clippy::integer_arithmetic, clippy::integer_division)]
+                    let (x, line) = if column / width > last_line {
+                        (width - 1, last_line)
+                    } else {
+                        (column % width, column / width)
+                    };
+                    self.terminal.cursor_position(&Position {
+                        x,
+                        y: (size.height as usize)
+                            .saturating_add(1)
+                            .saturating_add(line),
+                    });
+                }
+                _ => self.terminal.cursor_position(&Position {
+                    x: column.saturating_add(self.gutter_width()),
+                    y: self.cursor_position.y.saturating_sub(self.offset.y),
+                }),
+            }
+        }
+        self.update_cursor_shape();
+        self.terminal.cursor_show();
+        self.terminal.flush()
+    }
+
+    // A block while overwriting, a bar while inserting. The shape is only
+    // sent when it changes.
+    fn update_cursor_shape(&mut self) {
+        if !self.config.cursor_shape || self.should_quit {
+            return;
+        }
+        let shape = if self.overwrite {
+            CursorShape::Block
+        } else {
+            CursorShape::Bar
+        };
+        if self.cursor_shape != Some(shape) {
+            self.terminal.set_cursor_shape(shape);
+            self.cursor_shape = Some(shape);
+        }
+    }
+
+    fn draw_frame(&mut self, frame: Vec<String>) {
+        if self.previous_frame.len() != frame.len() {
+            self.previous_frame.clear();
         }
-        Terminal::cursor_show();
-        Terminal::flush()
+        for (index, line) in frame.iter().enumerate() {
+            if self.previous_frame.get(index) == Some(line) {
+                continue;
+            }
+            self.terminal.cursor_position(&Position { x: 0, y: index });
+            self.terminal.clear_current_line();
+            self.terminal.print(line);
+        }
+        self.previous_frame = frame;
     }
 
     #[allow(clippy::integer_division, clippy::integer_arithmetic)]
-    fn draw_rows(&self) {
+    fn render_rows(&self) -> Vec<String> {
         let height = self.terminal.size().height;
+        let mut lines = Vec::with_capacity(height as usize);
         for terminal_row in 0..height {
-            Terminal::clear_current_line();
-            if let Some(row) = self
-                .document
-                .row(self.offset.y.saturating_add(terminal_row as usize))
+            let index = self.offset.y.saturating_add(terminal_row as usize);
+            if let Some(row) = self.document.row(index) {
+                lines.push(format!(
+                    "{}{}",
+                    self.render_gutter(index),
+                    self.render_row(index, row)
+                ));
+            } else if self.document.file_name.is_none()
+                && self.document.is_empty()
+                && terminal_row == height / 3
+            {
+                lines.push(self.render_welcome_message());
+            } else if let Some(entry) = (terminal_row as usize)
+                .checked_sub(height as usize / 3 + 2)
+                .and_then(|index| self.render_recent_file(index))
             {
-                self.draw_row(row);
-            } else if terminal_row == height / 3 {
-                self.draw_welcome_message();
+                lines.push(entry);
             } else {
-                println!("~\r");
+                lines.push("~".to_string());
+            }
+            if let Some(line) = lines.last_mut() {
+                line.push_str(&self.render_minimap_row(terminal_row as usize));
             }
         }
+        lines
     }
 
-    pub fn draw_row(&self, row: &Row) {
-        let width = self.terminal.size().width as usize;
-        let start = self.offset.x;
-        let end = self.offset.x.saturating_add(width);
-        let row = row.render(start, end);
-        println!("{row}\r");
+    // Each minimap row sums up an equal share of the document: one mark per
+    // few columns of text, in the dimmed color of its most common
+    // highlighting. The rows on screen get a lighter background.
+    #[allow(clippy::integer_arithmetic, clippy::integer_division)]
+    fn render_minimap_row(&self, terminal_row: usize) -> String {
+        let width = self.minimap_width();
+        if width == 0 {
+            return String::new();
+        }
+        let height = (self.terminal.size().height as usize).max(1);
+        let rows_per_line = self.document.len().saturating_add(height - 1) / height;
+        let rows_per_line = rows_per_line.max(1);
+        let first = terminal_row * rows_per_line;
+        let last = first.saturating_add(rows_per_line);
+        let (mut indent, mut end) = (usize::MAX, 0);
+        let mut votes: Vec<(highlighting::Type, usize)> = Vec::new();
+        for row in (first..last).filter_map(|index| self.document.row(index)) {
+            let text = row.as_str().replace('\t', &" ".repeat(MINIMAP_SCALE));
+            if text.trim().is_empty() {
+                continue;
+            }
+            indent = indent.min(text.len() - text.trim_start().len());
+            end = end.max(text.trim_end().chars().count());
+            if let Some(hl_type) = row.dominant_highlighting() {
+                match votes.iter_mut().find(|(voted, _)| *voted == hl_type) {
+                    Some((_, count)) => *count += 1,
+                    None => votes.push((hl_type, 1)),
+                }
+            }
+        }
+        let color = votes.into_iter().max_by_key(|(_, count)| *count).map_or(
+            MINIMAP_COLOR,
+            |(hl_type, _)| {
+                let color::Rgb(r, g, b) = hl_type.to_rgb();
+                color::Rgb(r / 2, g / 2, b / 2)
+            },
+        );
+        let marks: String = (0..width.saturating_sub(1))
+            .map(|column| {
+                let text_column = column * MINIMAP_SCALE;
+                if text_column.saturating_add(MINIMAP_SCALE) > indent && text_column < end {
+                    '\u{25ac}'
+                } else {
+                    ' '
+                }
+            })
+            .collect();
+        let on_screen = first < self.offset.y.saturating_add(height)
+            && last > self.offset.y
+            && first < self.document.len().max(1);
+        let background = if on_screen {
+            format!("{}", color::Bg(MINIMAP_VIEWPORT_BG_COLOR))
+        } else {
+            String::new()
+        };
+        format!(
+            "{}{background}{}{marks}{}{}",
+            termion::cursor::Goto(
+                (self.terminal.size().width as usize - width + 2) as u16,
+                (terminal_row + 1) as u16
+            ),
+            color::Fg(color),
+            color::Fg(color::Reset),
+            color::Bg(color::Reset)
+        )
     }
 
-    fn draw_welcome_message(&self) {
-        let mut welcome_message = format!("RText editor -- version {VERSION}");
-        let width = self.terminal.size().width as usize;
-        let len = welcome_message.len();
-        #[allow(clippy::integer_arithmetic, clippy::integer_division)]
-        let padding = width.saturating_sub(len) / 2;
-        let spaces = " ".repeat(padding.saturating_sub(1));
-        welcome_message = format!("~{spaces}{welcome_message}");
-        welcome_message.truncate(width);
-        println!("{welcome_message}\r");
+    // Includes a blank column between the text and the minimap. Terminals
+    // too narrow to spare it go without.
+    fn minimap_width(&self) -> usize {
+        let width = self.config.minimap_width.saturating_add(1);
+        let terminal_width = self.terminal.size().width as usize;
+        if self.config.minimap && width.saturating_mul(3) <= terminal_width {
+            width
+        } else {
+            0
+        }
     }
 
-    fn draw_status_bar(&self) {
+    fn render_help(&self) -> Vec<String> {
         let width = self.terminal.size().width as usize;
-        let mut file_name = "[No Name]".to_string();
-        let modified_indicator = if self.document.is_dirty() {
-            " (modified)"
-        } else {
-            ""
-        };
+        let height = self.terminal.size().height as usize;
+        let key_width = KEYBINDINGS
+            .iter()
+            .map(|(key, _)| key.len())
+            .max()
+            .unwrap_or(0);
+        let mut lines = vec!["RText keybindings".to_string(), String::new()];
+        for (key, description) in KEYBINDINGS {
+            lines.push(format!("  {key:<key_width$}  {description}"));
+        }
+        lines.resize(height, String::new());
+        for line in &mut lines {
+            line.truncate(width);
+        }
+        self.push_bars(&mut lines, "Press any key to close the help.".to_string());
+        lines
+    }
 
-        let mut status;
-        if let Some(name) = &self.document.file_name {
-            file_name = name.clone();
-            file_name.truncate(20);
+    fn render_message_view(&self) -> Vec<String> {
+        let width = (self.terminal.size().width as usize).max(1);
+        let height = self.terminal.size().height as usize;
+        let mut lines = Vec::new();
+        for line in self.status_message.text.lines() {
+            let chars: Vec<char> = line.chars().collect();
+            if chars.is_empty() {
+                lines.push(String::new());
+            }
+            lines.extend(chars.chunks(width).map(|chunk| chunk.iter().collect()));
         }
-        status = format!("{} - {} lines", file_name, self.document.len());
-        let line_indicator = format!(
-            "{} | {}{}{}",
-            self.document.file_type(),
-            self.cursor_position.y.saturating_add(1),
-            self.document.len(),
-            modified_indicator
+        lines.resize(height, String::new());
+        self.push_bars(
+            &mut lines,
+            "Press any key to close the message.".to_string(),
         );
-
-        #[allow(clippy::integer_arithmetic)]
-        let len = status.len() + line_indicator.len();
-        status.push_str(&" ".repeat(width.saturating_sub(len)));
-        status = format!("{status}{line_indicator}");
-        status.truncate(width);
-
-        Terminal::set_bg_color(STATUS_BG_COLOR);
-        Terminal::set_fg_color(STATUS_FG_COLOR);
-        println!("{status}\r");
-        Terminal::reset_bg_color();
-        Terminal::reset_fg_color();
+        lines
     }
 
-    fn draw_message_bar(&self) {
-        Terminal::clear_current_line();
-        let message = &self.status_message;
-        if Instant::now() - message.time < Duration::new(5, 0) {
-            let mut text = message.text.clone();
-            text.truncate(self.terminal.size().width as usize);
-            print!("{text}");
+    // The status bar comes first and every bar row after it shows the message,
+    // wrapped at the terminal width.
+    fn push_bars(&self, frame: &mut Vec<String>, message: String) {
+        let bar_rows = self.terminal.bar_rows() as usize;
+        if bar_rows > 0 {
+            frame.push(self.render_status_bar());
         }
+        let message_rows = bar_rows.saturating_sub(1);
+        if message_rows == 0 {
+            return;
+        }
+        let width = (self.terminal.size().width as usize).max(1);
+        let chars: Vec<char> = message.chars().collect();
+        let mut lines: Vec<String> = chars
+            .chunks(width)
+            .map(|chunk| chunk.iter().collect())
+            .collect();
+        if lines.len() > message_rows {
+            // Leave room for a marker so it is clear there is more to see
+            // with Alt-m.
+            lines.truncate(message_rows);
+            if let Some(last) = lines.last_mut() {
+                let mut text: String = last.chars().take(width.saturating_sub(1)).collect();
+                text.push('…');
+                *last = text;
+            }
+        }
+        lines.resize(message_rows, String::new());
+        frame.extend(lines);
     }
 
-    fn prompt(
+    fn render_hex(&self, hex_view: &HexView) -> Result<Vec<String>, io::Error> {
+        let width = self.terminal.size().width as usize;
+        let height = self.terminal.size().height as usize;
+        let mut lines: Vec<String> = hex_view
+            .render(height)?
+            .into_iter()
+            .map(|line| line.chars().take(width).collect())
+            .collect();
+        lines.resize(height, "~".to_string());
+        let message = format!(
+            "Hex view: {} of {} rows (ESC to close)",
+            hex_view.row.saturating_add(1),
+            hex_view.len()
+        );
+        self.push_bars(&mut lines, message);
+        Ok(lines)
+    }
+
+    fn gutter_width(&self) -> usize {
+        if self.document.file_name.is_some() && !self.document.is_read_only() {
+            GUTTER_WIDTH
+        } else {
+            0
+        }
+    }
+
+    fn text_width(&self) -> usize {
+        (self.terminal.size().width as usize)
+            .saturating_sub(self.gutter_width())
+            .saturating_sub(self.minimap_width())
+    }
+
+    fn render_gutter(&self, index: usize) -> String {
+        let gutter_width = self.gutter_width();
+        if gutter_width == 0 {
+            return String::new();
+        }
+        let sign = self.document.sign(index);
+        format!(
+            "{}{:<gutter_width$}{}",
+            color::Fg(sign.to_color()),
+            sign.to_symbol(),
+            color::Fg(color::Reset)
+        )
+    }
+
+    fn render_row(&self, index: usize, row: &Row) -> String {
+        let width = self.text_width();
+        let start = self.offset.x;
+        let end = self.offset.x.saturating_add(width);
+        let quotes = if index == self.cursor_position.y {
+            self.document.quote_pair(&self.cursor_position)
+        } else {
+            None
+        };
+        // The cursor row is left alone so the cursor itself stays readable.
+        let crosshair = if self.config.crosshair && index != self.cursor_position.y {
+            self.document
+                .row(self.cursor_position.y)
+                .map(|cursor_row| cursor_row.columns(start, self.cursor_position.x))
+        } else {
+            None
+        };
+        let selection = self.selection().and_then(|(from, to)| {
+            (from.y..=to.y).contains(&index).then(|| {
+                (
+                    if index == from.y { from.x } else { 0 },
+                    if index == to.y { to.x } else { usize::MAX },
+                )
+            })
+        });
+        if !self.config.edge_markers || width < 2 {
+            return row.render(start, end, quotes, crosshair, selection);
+        }
+        // The markers take the place of the first and last text columns.
+        let left = start > 0 && !row.is_empty();
+        let right = row.columns(start, row.len()) > width;
+        let mut result = String::new();
+        let (start, crosshair) = if left {
+            result.push_str(&format!(
+                "{}\u{2039}{}",
+                color::Fg(EDGE_MARKER_COLOR),
+                color::Fg(color::Reset)
+            ));
+            let hidden = row.columns(start, start.saturating_add(1)).max(1);
+            (
+                start.saturating_add(1),
+                crosshair.and_then(|column| column.checked_sub(hidden)),
+            )
+        } else {
+            (start, crosshair)
+        };
+        let available = width
+            .saturating_sub(usize::from(left))
+            .saturating_sub(usize::from(right));
+        result.push_str(&row.render(
+            start,
+            start.saturating_add(available),
+            quotes,
+            crosshair,
+            selection,
+        ));
+        if right {
+            // A wide grapheme that doesn't fit leaves a gap before the marker.
+            let mut shown = 0_usize;
+            for index in start..row.len() {
+                let columns = row.columns(index, index.saturating_add(1));
+                if shown.saturating_add(columns) > available {
+                    break;
+                }
+                shown = shown.saturating_add(columns);
+            }
+            result.push_str(&" ".repeat(available.saturating_sub(shown)));
+            result.push_str(&format!(
+                "{}\u{203a}{}",
+                color::Fg(EDGE_MARKER_COLOR),
+                color::Fg(color::Reset)
+            ));
+        }
+        result
+    }
+
+    fn render_welcome_message(&self) -> String {
+        let welcome_message = match &self.config.welcome_message {
+            Some(message) => message.replace("{version}", VERSION),
+            None => format!("RText editor -- version {VERSION}"),
+        };
+        self.render_centered(&welcome_message)
+    }
+
+    fn render_recent_file(&self, index: usize) -> Option<String> {
+        let selected = self.welcome_selection?;
+        let recent = self.recent_files.existing();
+        let path = recent.get(index)?;
+        let marker = if index == selected { '>' } else { ' ' };
+        Some(self.render_centered(&format!("{marker} Alt-{} {path}", index.saturating_add(1))))
+    }
+
+    fn render_centered(&self, text: &str) -> String {
+        let width = self.terminal.size().width as usize;
+        let len = text.chars().count();
+        #[allow(clippy::integer_arithmetic, clippy::integer_division)]
+        let padding = width.saturating_sub(len) / 2;
+        let spaces = " ".repeat(padding.saturating_sub(1));
+        format!("~{spaces}{text}").chars().take(width).collect()
+    }
+
+    fn render_status_bar(&self) -> String {
+        let width = self.terminal.size().width as usize;
+        let mut file_name = "[No Name]".to_string();
+        let modified_indicator = if self.document.is_dirty() {
+            " (modified)"
+        } else {
+            ""
+        };
+
+        let mut status;
+        if let Some(name) = &self.document.file_name {
+            file_name = name.clone();
+            file_name.truncate(20);
+        }
+        let follow_indicator = match self.follow {
+            Some(true) => " (following)",
+            Some(false) => " (paused)",
+            None => "",
+        };
+        let overwrite_indicator = if self.overwrite { " [overwrite]" } else { "" };
+        let range_indicator = if self.search_range.is_some() {
+            " (in selection)"
+        } else {
+            ""
+        };
+        let buffer_indicator = if self.buffers.is_empty() {
+            String::new()
+        } else {
+            format!(
+                " [{}/{}]",
+                self.buffer_index.saturating_add(1),
+                self.buffer_count()
+            )
+        };
+        status = format!(
+            "{}{} - {} lines{}{}{}",
+            file_name,
+            buffer_indicator,
+            self.document.len(),
+            follow_indicator,
+            overwrite_indicator,
+            range_indicator
+        );
+        let line_indicator = format!(
+            "{} | {}{}{}",
+            self.document.file_type(),
+            self.cursor_position.y.saturating_add(1),
+            self.document.len(),
+            modified_indicator
+        );
+
+        let mut warnings = String::new();
+        if self.document.mixed_line_endings() {
+            warnings.push_str("mixed EOL | ");
+        }
+        if self.document.mixed_indent() {
+            warnings.push_str("mixed indent | ");
+        }
+        let file_info = format!(
+            "{}{} | {} {} | {}",
+            human_size(self.document.disk_size()),
+            if self.document.is_dirty() { "*" } else { "" },
+            self.document.encoding(),
+            self.document.line_ending().name(),
+            warnings
+        );
+        #[allow(clippy::integer_arithmetic)]
+        let line_indicator = if status.len() + file_info.len() + line_indicator.len() < width {
+            format!("{file_info}{line_indicator}")
+        } else {
+            line_indicator
+        };
+
+        #[allow(clippy::integer_arithmetic)]
+        let len = status.len() + line_indicator.len();
+        status.push_str(&" ".repeat(width.saturating_sub(len)));
+        status = format!("{status}{line_indicator}");
+        status.truncate(width);
+
+        format!(
+            "{}{}{status}{}{}",
+            color::Bg(STATUS_BG_COLOR),
+            color::Fg(STATUS_FG_COLOR),
+            color::Bg(color::Reset),
+            color::Fg(color::Reset)
+        )
+    }
+
+    fn render_message_bar(&self) -> String {
+        let message = &self.status_message;
+        if Instant::now() - message.time < Duration::new(5, 0) {
+            message.text.clone()
+        } else {
+            String::new()
+        }
+    }
+
+    fn prompt(
         &mut self,
         prompt: &str,
         mut callback: impl FnMut(&mut Self, Key, &String),
     ) -> Result<Option<String>, io::Error> {
         let mut result = String::new();
+        // Counted in chars; a tab shows up as the two columns of `^I`.
+        let mut cursor = 0_usize;
         loop {
-            self.status_message = StatusMessage::from(format!("{prompt}{result}"));
+            self.status_message =
+                StatusMessage::from(format!("{prompt}{}", result.replace('\t', "^I")));
+            let before: usize = result
+                .chars()
+                .take(cursor)
+                .map(|c| if c == '\t' { 2 } else { 1 })
+                .sum();
+            self.prompt_cursor = Some(prompt.chars().count().saturating_add(before));
             self.refresh_screen()?;
-            let key = Terminal::read_key()?;
+            let key = self.terminal.read_key()?;
+            let at = result
+                .char_indices()
+                .nth(cursor)
+                .map_or(result.len(), |(index, _)| index);
             match key {
                 Key::Char('\n') => break,
-                Key::Backspace => {
-                    result.pop();
+                Key::Backspace if cursor > 0 => {
+                    cursor = cursor.saturating_sub(1);
+                    if let Some((index, _)) = result.char_indices().nth(cursor) {
+                        result.remove(index);
+                    }
                 }
+                Key::Delete if at < result.len() => {
+                    result.remove(at);
+                }
+                Key::Left => cursor = cursor.saturating_sub(1),
+                Key::Right => cursor = cursor.saturating_add(1).min(result.chars().count()),
+                Key::Home => cursor = 0,
+                Key::End => cursor = result.chars().count(),
                 Key::Esc => {
                     result.truncate(0);
                     break;
                 }
-                Key::Char(c) if !c.is_control() => result.push(c),
+                Key::Char(c) if c == '\t' || !c.is_control() => {
+                    result.insert(at, c);
+                    cursor = cursor.saturating_add(1);
+                }
                 _ => (),
             };
             callback(self, key, &result);
         }
+        self.prompt_cursor = None;
         self.status_message = StatusMessage::from(String::new());
         if result.is_empty() {
             Ok(None)
@@ -361,45 +1539,798 @@ impl Editor {
         }
     }
 
-    fn save(&mut self) {
+    // Returns whether the file was written.
+    fn save(&mut self) -> bool {
         if self.document.file_name.is_none() {
             let new_name = self.prompt("Save as: ", |_, _, _| {}).unwrap_or(None);
-            if new_name.is_none() {
+            let new_name = match new_name {
+                Some(new_name)
+                    if !self.replaces_other_file(&new_name)
+                        || self.confirm(&format!("'{new_name}' exists. Overwrite? (y/n): ")) =>
+                {
+                    new_name
+                }
+                _ => {
+                    self.status_message = StatusMessage::from("Save aborted.".to_string());
+                    return false;
+                }
+            };
+            self.document.file_name = Some(new_name);
+        }
+        let mut format_error = None;
+        if let Some(command) = self.config.format_command(&self.document.file_type()) {
+            format_error = self.document.format(command).err();
+            self.clamp_cursor();
+        }
+        match self.document.save() {
+            Ok(_) => {
+                if let Some(file_name) = &self.document.file_name {
+                    swap::remove(file_name);
+                }
+                self.apply_config();
+                self.status_message = StatusMessage::from(match format_error {
+                    Some(err) => format!("File saved without formatting: {err}"),
+                    None => "File saved successfully.".to_string(),
+                });
+                true
+            }
+            Err(err) => {
+                self.status_message =
+                    StatusMessage::from(format!("Error writing file: {}", describe_io_error(&err)));
+                false
+            }
+        }
+    }
+
+    fn replaces_other_file(&self, name: &str) -> bool {
+        let cwd = env::current_dir().unwrap_or_default();
+        let current = self
+            .document
+            .file_name
+            .as_deref()
+            .map(|current| resolve_path(current, &cwd));
+        replaces_other_file(&resolve_path(name, &cwd), current.as_deref(), Path::exists)
+    }
+
+    fn save_with_encoding(&mut self) {
+        let name = self
+            .prompt(
+                "Save with encoding (UTF-8, Latin-1, UTF-16LE, UTF-16BE): ",
+                |_, _, _| {},
+            )
+            .unwrap_or(None);
+        let name = match name {
+            Some(name) => name,
+            None => {
                 self.status_message = StatusMessage::from("Save aborted.".to_string());
                 return;
             }
-            self.document.file_name = new_name;
+        };
+        match Encoding::from_name(&name) {
+            Some(encoding) => {
+                // A save that is aborted or fails leaves the encoding as it was.
+                let previous = self.document.set_encoding(encoding);
+                if !self.save() {
+                    self.document.set_encoding(previous);
+                }
+            }
+            None => {
+                self.status_message = StatusMessage::from(format!("Unknown encoding '{name}'."));
+            }
         }
-        match self.document.save() {
-            Ok(_) => {
-                self.status_message = StatusMessage::from("File saved successfully.".to_string())
+    }
+
+    // Input is read blockingly, so the swap file is refreshed on the first
+    // keypress after the interval has passed rather than on a timer.
+    fn write_swap(&mut self) {
+        let interval = Duration::from_secs(self.config.swap_interval);
+        if self.config.swap_interval == 0
+            || !self.document.is_dirty()
+            || self.last_swap.elapsed() < interval
+        {
+            return;
+        }
+        if let Some(file_name) = &self.document.file_name {
+            if swap::write(file_name, &self.document.content()).is_err() {
+                self.status_message =
+                    StatusMessage::from("ERR: Could not write the swap file.".to_string());
+            }
+        }
+        self.last_swap = Instant::now();
+    }
+
+    fn offer_recovery(&mut self) {
+        let file_name = match &self.document.file_name {
+            Some(file_name) if !self.document.is_read_only() => file_name.clone(),
+            _ => return,
+        };
+        let content = match swap::read_newer(&file_name) {
+            Some(content) if content != self.document.content() => content,
+            _ => return,
+        };
+        if self.confirm("Found unsaved changes from a previous session. Recover them? (y/n): ") {
+            self.document.replace_content(&content);
+            self.clamp_cursor();
+            self.status_message = StatusMessage::from("Recovered unsaved changes.".to_string());
+        } else {
+            swap::remove(&file_name);
+            self.status_message = StatusMessage::from("Discarded the swap file.".to_string());
+        }
+    }
+
+    fn confirm(&mut self, question: &str) -> bool {
+        let answer = self.prompt(question, |_, _, _| {}).unwrap_or(None);
+        answer.map_or(false, |answer| answer.eq_ignore_ascii_case("y"))
+    }
+
+    fn replace_all(&mut self) {
+        if self.document.is_read_only() {
+            self.status_message = StatusMessage::from("File is opened read-only.".to_string());
+            return;
+        }
+        let selection_start = self.selection_start;
+        let range = self.take_search_range();
+        let query = match self.prompt("Replace all: ", |_, _, _| {}).unwrap_or(None) {
+            Some(query) => query,
+            None => {
+                self.search_range = None;
+                self.selection_start = selection_start;
+                return;
+            }
+        };
+        let replacement = match self
+            .prompt(&format!("Replace '{query}' with: "), |_, _, _| {})
+            .unwrap_or(None)
+        {
+            Some(replacement) => replacement,
+            None => {
+                self.search_range = None;
+                self.selection_start = selection_start;
+                self.status_message = StatusMessage::from("Replace aborted.".to_string());
+                return;
+            }
+        };
+        self.search_range = None;
+        let count = self.document.replace_all(
+            &query,
+            &replacement,
+            self.config.case_sensitive(&query),
+            range.as_ref(),
+        );
+        self.clamp_cursor();
+        self.status_message = StatusMessage::from(match count {
+            0 => format!("No matches for '{query}'."),
+            1 => "Replaced 1 match.".to_string(),
+            _ => format!("Replaced {count} matches."),
+        });
+    }
+
+    // Walks the matches from the top of the file, asking before each one.
+    fn replace_one_by_one(&mut self) -> Result<(), io::Error> {
+        if self.document.is_read_only() {
+            self.status_message = StatusMessage::from("File is opened read-only.".to_string());
+            return Ok(());
+        }
+        let selection_start = self.selection_start;
+        self.take_search_range();
+        let query = match self.prompt("Replace: ", |_, _, _| {})? {
+            Some(query) => query,
+            None => {
+                self.search_range = None;
+                self.selection_start = selection_start;
+                return Ok(());
+            }
+        };
+        let replacement = match self.prompt(&format!("Replace '{query}' with: "), |_, _, _| {})? {
+            Some(replacement) => replacement,
+            None => {
+                self.search_range = None;
+                self.selection_start = selection_start;
+                self.status_message = StatusMessage::from("Replace aborted.".to_string());
+                return Ok(());
+            }
+        };
+        let case_sensitive = self.config.case_sensitive(&query);
+        let undo_depth = self.document.undo_depth();
+        let old_position = self.cursor_position;
+        let mut at = self
+            .search_range
+            .map_or_else(Position::default, |(start, _)| start);
+        let mut all = false;
+        let mut matched = false;
+        let mut count: usize = 0;
+        self.highlighted_word = Some(query.clone());
+        while let Some(found) = self.document.find(
+            &query,
+            &at,
+            SearchDirection::Forward,
+            case_sensitive,
+            self.search_range.as_ref(),
+        ) {
+            matched = true;
+            self.cursor_position = found;
+            self.scroll();
+            if !all {
+                self.status_message = StatusMessage::from(format!(
+                    "Replace with '{replacement}'? (y/n/a, ESC to stop)"
+                ));
+                self.refresh_screen()?;
+                let answer = loop {
+                    match self.terminal.read_key()? {
+                        key @ (Key::Char('y' | 'n' | 'a') | Key::Esc) => break key,
+                        _ => (),
+                    }
+                };
+                match answer {
+                    Key::Esc => break,
+                    Key::Char('n') => {
+                        at = Position {
+                            x: found.x.saturating_add(1),
+                            y: found.y,
+                        };
+                        continue;
+                    }
+                    Key::Char('a') => all = true,
+                    _ => (),
+                }
+            }
+            let len = self
+                .document
+                .row(found.y)
+                .and_then(|row| row.match_len(found.x, &query, case_sensitive))
+                .unwrap_or(0);
+            match self.document.replace_at(&found, len, &replacement) {
+                Some(end) => {
+                    count = count.saturating_add(1);
+                    at = end;
+                }
+                None => break,
+            }
+            // The replacement shifts what follows it on the row, including
+            // the end of the selection.
+            if let Some((_, range_end)) = self.search_range.as_mut() {
+                if range_end.y == found.y {
+                    range_end.x = range_end
+                        .x
+                        .saturating_sub(found.x.saturating_add(len))
+                        .saturating_add(at.x);
+                }
+            }
+        }
+        self.search_range = None;
+        self.document.join_edits(undo_depth);
+        self.clear_search_highlight();
+        if count == 0 {
+            self.cursor_position = old_position;
+        } else {
+            self.record_edit();
+        }
+        self.clamp_cursor();
+        self.scroll();
+        self.status_message = StatusMessage::from(match count {
+            _ if !matched => format!("No matches for '{query}'."),
+            1 => "Replaced 1 match.".to_string(),
+            _ => format!("Replaced {count} matches."),
+        });
+        Ok(())
+    }
+
+    // Replaces the word before the cursor with its snippet from the config and
+    // puts the cursor where the first `${...}` placeholder was.
+    fn expand_snippet(&mut self) {
+        if self.document.is_read_only() {
+            self.status_message = StatusMessage::from("File is opened read-only.".to_string());
+            return;
+        }
+        let Position { x, y } = self.cursor_position;
+        let (name, indent) = match self.document.row(y) {
+            Some(row) => {
+                let before: Vec<&str> = row.as_str().graphemes(true).take(x).collect();
+                let word_len = before
+                    .iter()
+                    .rev()
+                    .take_while(|g| g.chars().all(|c| c.is_alphanumeric() || c == '_'))
+                    .count();
+                let name = before[before.len().saturating_sub(word_len)..].concat();
+                let line = row.as_str();
+                let indent = &line[..line.len().saturating_sub(line.trim_start().len())];
+                (name, indent.to_string())
             }
-            Err(_) => self.status_message = StatusMessage::from("Error writing file!".to_string()),
+            None => (String::new(), String::new()),
         };
+        if name.is_empty() {
+            self.status_message =
+                StatusMessage::from("No word before the cursor to expand.".to_string());
+            return;
+        }
+        let (text, stop) = match self.config.snippet(&name) {
+            Some(body) => fill_snippet(body, &indent, &self.document.indent_unit()),
+            None => {
+                self.status_message = StatusMessage::from(format!("No snippet named '{name}'."));
+                return;
+            }
+        };
+        let start = Position {
+            x: x.saturating_sub(name.graphemes(true).count()),
+            y,
+        };
+        // The trigger word and the snippet text go in one undo step.
+        let undo_depth = self.document.undo_depth();
+        self.document.delete_range(&start, &self.cursor_position);
+        let inserted = self.document.insert_text(&start, &text);
+        self.document.join_edits(undo_depth);
+        match inserted {
+            Ok(end) => {
+                let cursor = stop.map_or(end, |stop| {
+                    let before = &text[..stop];
+                    let line = before.rsplit('\n').next().unwrap_or(before);
+                    let lines = before.matches('\n').count();
+                    Position {
+                        x: if lines == 0 { start.x } else { 0 }
+                            .saturating_add(line.graphemes(true).count()),
+                        y: y.saturating_add(lines),
+                    }
+                });
+                self.set_cursor(cursor);
+            }
+            Err(err) => self.status_message = StatusMessage::from(format!("ERR: {err}")),
+        }
+    }
+
+    fn retab(&mut self) {
+        if self.document.is_read_only() {
+            self.status_message = StatusMessage::from("File is opened read-only.".to_string());
+            return;
+        }
+        let answer = self
+            .prompt("Convert indentation to (s)paces or (t)abs: ", |_, _, _| {})
+            .unwrap_or(None);
+        let to_spaces = match answer.as_deref().map(str::to_lowercase).as_deref() {
+            Some("s") => true,
+            Some("t") => false,
+            _ => {
+                self.status_message = StatusMessage::from("Conversion aborted.".to_string());
+                return;
+            }
+        };
+        let width = self.document.indent_size();
+        let count = self.document.retab(to_spaces, width);
+        self.clamp_cursor();
+        self.status_message = StatusMessage::from(match count {
+            0 => "No indentation to convert.".to_string(),
+            1 => "Converted the indentation of 1 line.".to_string(),
+            _ => format!("Converted the indentation of {count} lines."),
+        });
+    }
+
+    // Only a failure to start the opener is reported, not what it does with
+    // the URL afterwards.
+    fn open_url(&mut self) {
+        let url = match self.document.url_at(&self.cursor_position) {
+            Some(url) => url,
+            None => {
+                self.status_message = StatusMessage::from("No URL under the cursor.".to_string());
+                return;
+            }
+        };
+        let opener = if cfg!(target_os = "macos") {
+            "open"
+        } else {
+            "xdg-open"
+        };
+        let result = Command::new(opener)
+            .arg(&url)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        self.status_message = StatusMessage::from(match result {
+            Ok(mut child) => {
+                // Reap it once it exits rather than leave a zombie behind.
+                thread::spawn(move || child.wait());
+                format!("Opening {url}")
+            }
+            Err(err) => format!("ERR: Could not open {url}: {err}"),
+        });
+    }
+
+    fn write_copy(&mut self) {
+        let path = self
+            .prompt("Write a copy to: ", |_, _, _| {})
+            .unwrap_or(None);
+        let message = match path {
+            None => "Write aborted.".to_string(),
+            Some(path) => match self.document.write_copy(&path) {
+                Ok(_) => format!("Wrote a copy to '{path}'."),
+                Err(err) => format!("ERR: Could not write '{path}': {}", describe_io_error(&err)),
+            },
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+
+    fn filter_buffer(&mut self) {
+        if self.document.is_read_only() {
+            self.status_message = StatusMessage::from("File is opened read-only.".to_string());
+            return;
+        }
+        let command = self
+            .prompt("Filter through command: ", |_, _, _| {})
+            .unwrap_or(None);
+        if let Some(command) = command {
+            match filter::pipe(&command, &self.document.content()) {
+                Ok(output) => {
+                    self.document.replace_content(&output);
+                    self.clamp_cursor();
+                    self.status_message =
+                        StatusMessage::from(format!("Filtered buffer through '{command}'."));
+                }
+                Err(err) => self.status_message = StatusMessage::from(format!("ERR: {err}")),
+            }
+        }
+    }
+
+    fn undo(&mut self) {
+        match self.document.undo() {
+            Some(position) => self.set_cursor(position),
+            None => self.status_message = StatusMessage::from("Nothing to undo.".to_string()),
+        }
+    }
+
+    fn redo(&mut self) {
+        match self.document.redo() {
+            Some(position) => self.set_cursor(position),
+            None => self.status_message = StatusMessage::from("Nothing to redo.".to_string()),
+        }
+    }
+
+    fn clamp_cursor(&mut self) {
+        let y = self.cursor_position.y.min(self.document.len());
+        let width = self.document.row(y).map_or(0, Row::len);
+        self.cursor_position = Position {
+            x: self.cursor_position.x.min(width),
+            y,
+        };
+        self.scroll();
+    }
+
+    fn open_companion(&mut self) {
+        let companion = self.document.file_name.as_ref().and_then(|file_name| {
+            self.config
+                .companion_paths(file_name)
+                .into_iter()
+                .find(|path| Path::new(path).is_file())
+        });
+        match companion {
+            Some(path) => self.open(&path),
+            None => self.status_message = StatusMessage::from("No companion file".to_string()),
+        }
+    }
+
+    fn open_file(&mut self) {
+        if let Some(path) = self.prompt("Open: ", |_, _, _| {}).unwrap_or(None) {
+            self.open(&path);
+        }
+    }
+
+    // Opens `path` in a buffer after the shown one, or shows the buffer it is
+    // already open in. An untouched empty buffer is replaced instead of kept.
+    fn open(&mut self, path: &str) {
+        let open_in = (0..self.buffer_count()).find(|index| {
+            self.buffer_document(*index)
+                .and_then(|document| document.file_name.as_deref())
+                == Some(path)
+        });
+        if let Some(index) = open_in {
+            self.show_buffer(index);
+            self.status_message = StatusMessage::from(format!("Switched to '{path}'."));
+            return;
+        }
+        let document = match Document::open(path) {
+            Ok(document) => document,
+            Err(_) => {
+                self.status_message =
+                    StatusMessage::from(format!("ERR: Could not open file '{path}'"));
+                return;
+            }
+        };
+        let previous = self.replace_buffer(Buffer {
+            document,
+            ..Buffer::default()
+        });
+        if previous.document.file_name.is_some()
+            || previous.document.is_dirty()
+            || !previous.document.is_empty()
+        {
+            self.buffers.insert(self.buffer_index, previous);
+            self.buffer_index = self.buffer_index.saturating_add(1);
+        }
+        self.recent_files.add(path);
+        self.status_message = StatusMessage::from(format!("Opened '{path}'."));
+        self.apply_config();
+    }
+
+    // The names of the buffers with unsaved changes, in buffer order.
+    fn unsaved_buffers(&self) -> Vec<String> {
+        (0..self.buffer_count())
+            .filter_map(|index| self.buffer_document(index))
+            .filter(|document| document.is_dirty())
+            .map(|document| {
+                document
+                    .file_name
+                    .as_deref()
+                    .map_or("[No Name]", |name| {
+                        Path::new(name)
+                            .file_name()
+                            .and_then(|name| name.to_str())
+                            .unwrap_or(name)
+                    })
+                    .to_string()
+            })
+            .collect()
+    }
+
+    fn buffer_count(&self) -> usize {
+        self.buffers.len().saturating_add(1)
+    }
+
+    // The document of the buffer at `index`, counting the shown one.
+    fn buffer_document(&self, index: usize) -> Option<&Document> {
+        let buffer = match index.cmp(&self.buffer_index) {
+            Ordering::Less => self.buffers.get(index),
+            Ordering::Equal => return Some(&self.document),
+            Ordering::Greater => self.buffers.get(index.saturating_sub(1)),
+        };
+        buffer.map(|buffer| &buffer.document)
+    }
+
+    fn switch_buffer(&mut self, forward: bool) {
+        let count = self.buffer_count();
+        if count == 1 {
+            self.status_message = StatusMessage::from("No other buffer is open.".to_string());
+            return;
+        }
+        #[allow(clippy::integer_arithmetic)]
+        let index = if forward {
+            (self.buffer_index + 1) % count
+        } else {
+            (self.buffer_index + count - 1) % count
+        };
+        self.show_buffer(index);
+    }
+
+    // Shows the buffer at `index`, counting the shown one, and keeps the
+    // order of the others.
+    fn show_buffer(&mut self, index: usize) {
+        if index == self.buffer_index || index >= self.buffer_count() {
+            return;
+        }
+        let current = self.replace_buffer(Buffer::default());
+        self.buffers.insert(self.buffer_index, current);
+        let target = self.buffers.remove(index);
+        self.buffer_index = index;
+        self.replace_buffer(target);
+        self.apply_config();
+    }
+
+    // Shows `buffer` in place of the shown one, which is returned. What
+    // belongs to a single document, like a selection, doesn't carry over.
+    fn replace_buffer(&mut self, buffer: Buffer) -> Buffer {
+        self.selection_start = None;
+        self.hex_view = None;
+        self.desired_x = None;
+        self.edit_jump = None;
+        self.highlighted_word = None;
+        self.killing = false;
+        self.welcome_selection = None;
+        Buffer {
+            document: std::mem::replace(&mut self.document, buffer.document),
+            cursor_position: std::mem::replace(&mut self.cursor_position, buffer.cursor_position),
+            offset: std::mem::replace(&mut self.offset, buffer.offset),
+            edit_locations: std::mem::replace(&mut self.edit_locations, buffer.edit_locations),
+        }
+    }
+
+    // Hands the per-document options to the document. The `[file_types]`
+    // section overrides the detected highlighter; unknown highlighter names
+    // are reported, not ignored.
+    fn apply_config(&mut self) {
+        self.document
+            .set_preserve_line_endings(self.config.preserve_line_endings);
+        let name = match &self.document.file_name {
+            Some(file_name) => self.config.file_type_for(file_name),
+            None => None,
+        };
+        if let Some(name) = name {
+            match FileType::from_name(name) {
+                Some(file_type) => self.document.set_file_type(file_type),
+                None => {
+                    self.status_message = StatusMessage::from(format!(
+                        "Unknown highlighter '{name}' in [file_types]."
+                    ));
+                }
+            }
+        }
+    }
+
+    // Closing a buffer shows the one before it. Closing the last one leaves
+    // an empty buffer and brings back the list of recent files instead of
+    // quitting.
+    fn close_file(&mut self) {
+        if self.document.is_dirty() {
+            self.status_message =
+                StatusMessage::from("Save your changes before closing the file.".to_string());
+            return;
+        }
+        if let Some(file_name) = &self.document.file_name {
+            swap::remove(file_name);
+        }
+        if self.buffers.is_empty() {
+            self.replace_buffer(Buffer::default());
+            if !self.recent_files.existing().is_empty() {
+                self.welcome_selection = Some(0);
+            }
+        } else {
+            let index = self.buffer_index.saturating_sub(1);
+            let neighbour = self.buffers.remove(index);
+            self.buffer_index = index;
+            self.replace_buffer(neighbour);
+            self.apply_config();
+        }
+        self.status_message = StatusMessage::from("Closed the file.".to_string());
+    }
+
+    fn toggle_follow(&mut self) {
+        let message = match self.follow {
+            Some(true) => {
+                self.follow = Some(false);
+                "Stopped following the file."
+            }
+            Some(false) => {
+                self.follow = Some(true);
+                self.refresh_follow();
+                self.follow_end();
+                "Following the file."
+            }
+            None => "Start RText with --follow to follow a file.",
+        };
+        self.status_message = StatusMessage::from(message.to_string());
+    }
+
+    fn refresh_follow(&mut self) {
+        match self.document.refresh() {
+            Ok(true) => self.follow_end(),
+            Ok(false) => (),
+            Err(err) => self.status_message = StatusMessage::from(format!("ERR: {err}")),
+        }
+    }
+
+    fn follow_end(&mut self) {
+        self.set_cursor(Position {
+            x: 0,
+            y: self.document.len().saturating_sub(1),
+        });
+    }
+
+    fn toggle_hex_view(&mut self) {
+        let hex_view = match &self.document.file_name {
+            Some(file_name) => HexView::open(Path::new(file_name)),
+            None => {
+                self.status_message = StatusMessage::from("No file to inspect.".to_string());
+                return;
+            }
+        };
+        match hex_view {
+            Ok(hex_view) => {
+                self.hex_view = Some(hex_view);
+                if self.document.is_dirty() {
+                    self.status_message =
+                        StatusMessage::from("Hex view shows the file on disk.".to_string());
+                }
+            }
+            Err(err) => self.status_message = StatusMessage::from(format!("ERR: {err}")),
+        }
+    }
+
+    fn toggle_spell_check(&mut self) {
+        let message = if self.dictionary.take().is_some() {
+            "Spell check disabled.".to_string()
+        } else {
+            match Dictionary::load(&self.config.dictionary_path) {
+                Ok(dictionary) => {
+                    self.dictionary = Some(dictionary);
+                    "Spell check enabled.".to_string()
+                }
+                Err(_) => format!(
+                    "Could not load dictionary '{}'",
+                    self.config.dictionary_path
+                ),
+            }
+        };
+        self.document.unhighlight_all();
+        self.status_message = StatusMessage::from(message);
+    }
+
+    fn preview_diff(&mut self) {
+        let message = match self.document.diff_with_disk() {
+            Ok(summary) if summary.added == 0 && summary.removed == 0 => {
+                "No changes to save.".to_string()
+            }
+            Ok(summary) => format!(
+                "+{} -{} lines changed since last save.",
+                summary.added, summary.removed
+            ),
+            Err(_) => "Error reading file from disk!".to_string(),
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+
+    fn jump_to_bracket(&mut self) {
+        if !matches!(self.char_at(0), Some('(' | ')' | '[' | ']' | '{' | '}')) {
+            self.status_message = StatusMessage::from("Not on a bracket.".to_string());
+            return;
+        }
+        match self.document.find_matching_bracket(&self.cursor_position) {
+            Some(position) => self.set_cursor(position),
+            None => self.status_message = StatusMessage::from("No matching bracket.".to_string()),
+        }
+    }
+
+    // Lines past the end go to the last one.
+    fn go_to_line(&mut self) {
+        let answer = match self.prompt("Go to line: ", |_, _, _| {}).unwrap_or(None) {
+            Some(answer) => answer,
+            None => return,
+        };
+        match answer.trim().parse::<usize>() {
+            Ok(line) if line > 0 => {
+                let y = line.min(self.document.len()).saturating_sub(1);
+                self.set_cursor(Position { x: 0, y });
+            }
+            _ => {
+                self.status_message =
+                    StatusMessage::from(format!("'{answer}' is not a line number."));
+            }
+        }
     }
 
     fn search(&mut self) {
         let old_postion = self.cursor_position;
+        let selection_start = self.selection_start;
+        self.take_search_range();
+        // Alt-c only changes how this search matches case.
+        let case_setting = self.config.case_sensitive;
         let mut direction = SearchDirection::Forward;
         let query = self
             .prompt(
-                "Search (ESC to cancel, Arrows to navigate): ",
+                "Search (ESC to cancel, Up/Down to navigate, Alt-c for case): ",
                 |editor, key, query| {
                     let mut moved = false;
                     match key {
-                        Key::Right | Key::Down => {
+                        Key::Down => {
                             direction = SearchDirection::Forward;
                             editor.move_cursor(Key::Right);
                             moved = true;
                         }
-                        Key::Left | Key::Up => direction = SearchDirection::Backward,
+                        Key::Up => direction = SearchDirection::Backward,
+                        // Smart case, then matching case, then ignoring it.
+                        Key::Alt('c') => {
+                            editor.config.case_sensitive = match editor.config.case_sensitive {
+                                None => Some(true),
+                                Some(true) => Some(false),
+                                Some(false) => None,
+                            };
+                            direction = SearchDirection::Forward;
+                        }
                         _ => direction = SearchDirection::Forward,
                     }
-                    if let Some(position) =
-                        editor
-                            .document
-                            .find(&query, &editor.cursor_position, direction)
-                    {
+                    let case_sensitive = editor.config.case_sensitive(query);
+                    if let Some(position) = editor.document.find(
+                        query,
+                        &editor.cursor_position,
+                        direction,
+                        case_sensitive,
+                        editor.search_range.as_ref(),
+                    ) {
                         editor.cursor_position = position;
                         editor.scroll();
                     } else if moved {
@@ -409,15 +2340,446 @@ impl Editor {
                 },
             )
             .unwrap_or(None);
+        self.config.case_sensitive = case_setting;
+        self.search_range = None;
         if query.is_none() {
+            self.selection_start = selection_start;
             self.cursor_position = old_postion;
             self.scroll();
         }
+        self.clear_search_highlight();
+    }
+
+    // Rows highlighted without a word keep their cached colors, so the match
+    // coloring only goes away once every row is highlighted afresh.
+    fn clear_search_highlight(&mut self) {
         self.highlighted_word = None;
+        self.document.unhighlight_all();
+    }
+
+    #[allow(
+        clippy::integer_division,
+        clippy::integer_arithmetic,
+        clippy::indexing_slicing
+    )]
+    fn list_matches(&mut self) -> Result<(), io::Error> {
+        let query = match self.prompt("List matches for: ", |_, _, _| {})? {
+            Some(query) => query,
+            None => return Ok(()),
+        };
+        let matches =
+            self.document
+                .find_all(&query, MAX_MATCHES, self.config.case_sensitive(&query));
+        if matches.is_empty() {
+            self.status_message = StatusMessage::from(format!("No matches for '{query}'."));
+            return Ok(());
+        }
+        let page = (self.terminal.size().height as usize).max(1);
+        let last = matches.len().saturating_sub(1);
+        let mut selected = 0;
+        loop {
+            let frame = self.render_matches(&matches, selected);
+            self.terminal.cursor_hide();
+            self.draw_frame(frame);
+            self.terminal.flush()?;
+            match self.terminal.read_key()? {
+                Key::Up => selected = selected.saturating_sub(1),
+                Key::Down => selected = selected.saturating_add(1).min(last),
+                Key::PageUp => selected = selected.saturating_sub(page),
+                Key::PageDown => selected = selected.saturating_add(page).min(last),
+                Key::Char('\n') => {
+                    if let Some(position) = matches.get(selected) {
+                        self.cursor_position = *position;
+                        self.scroll();
+                    }
+                    break;
+                }
+                Key::Esc => break,
+                _ => (),
+            }
+        }
+        self.status_message = StatusMessage::from(String::new());
+        Ok(())
+    }
+
+    #[allow(clippy::integer_division, clippy::integer_arithmetic)]
+    fn render_matches(&self, matches: &[Position], selected: usize) -> Vec<String> {
+        let width = self.terminal.size().width as usize;
+        let height = self.terminal.size().height as usize;
+        let first = selected / height.max(1) * height;
+        let mut lines: Vec<String> = matches
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(height)
+            .map(|(index, position)| {
+                let marker = if index == selected { '>' } else { ' ' };
+                let text = self.document.read_row(position.y).unwrap_or_default();
+                format!(
+                    "{marker} {:>5}: {}",
+                    position.y.saturating_add(1),
+                    text.as_str().trim()
+                )
+                .chars()
+                .map(|c| if c.is_control() { '?' } else { c })
+                .take(width)
+                .collect()
+            })
+            .collect();
+        lines.resize(height, "~".to_string());
+        let capped = if matches.len() >= MAX_MATCHES {
+            format!(" (first {MAX_MATCHES})")
+        } else {
+            String::new()
+        };
+        let message = format!(
+            "Match {}/{}{capped} (Enter to jump, ESC to cancel)",
+            selected.saturating_add(1),
+            matches.len()
+        );
+        self.push_bars(&mut lines, message);
+        lines
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len().saturating_sub(1) {
+        size /= 1024.0;
+        unit = unit.saturating_add(1);
     }
+    format!("{size:.1} {}", UNITS.get(unit).unwrap_or(&""))
+}
+
+// Builds an editor without touching the command line or the real terminal,
+// for embedding and tests. Without a terminal, build() opens the real one.
+#[derive(Default, Debug)]
+pub struct EditorBuilder {
+    terminal: Option<Terminal>,
+    document: Document,
+    config: Config,
+    cursor: Option<Position>,
+    follow: bool,
+}
+
+impl EditorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn terminal(mut self, terminal: Terminal) -> Self {
+        self.terminal = Some(terminal);
+        self
+    }
+
+    pub fn size(self, size: Size) -> Self {
+        self.terminal(Terminal::scripted(size, Vec::new(), io::sink()))
+    }
+
+    pub fn document(mut self, document: Document) -> Self {
+        self.document = document;
+        self
+    }
+
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn cursor(mut self, position: Position) -> Self {
+        self.cursor = Some(position);
+        self
+    }
+
+    // Keeps the view at the end of a document opened with
+    // `Document::open_read_only`, picking up lines appended to the file.
+    pub fn follow(mut self, follow: bool) -> Self {
+        self.follow = follow;
+        self
+    }
+
+    pub fn build(self) -> Editor {
+        let terminal = self
+            .terminal
+            .unwrap_or_else(|| Terminal::new().expect("failed to initialize terminal"));
+        let mut editor = Editor::from_parts(terminal, self.document, self.config);
+        editor.apply_config();
+        if self.follow {
+            editor.follow = Some(true);
+            editor.follow_end();
+        }
+        if let Some(position) = self.cursor {
+            editor.cursor_position = position;
+            editor.clamp_cursor();
+            editor.scroll_cursor_to(editor.terminal.size().height as usize / 2);
+        }
+        editor
+    }
+}
+
+// Says what went wrong in words that suggest a fix, falling back to the
+// system's own description for anything less common.
+fn describe_io_error(err: &io::Error) -> String {
+    match err.kind() {
+        io::ErrorKind::PermissionDenied => "Permission denied.".to_string(),
+        io::ErrorKind::NotFound => "No such directory.".to_string(),
+        io::ErrorKind::ReadOnlyFilesystem => "The file system is read-only.".to_string(),
+        io::ErrorKind::StorageFull => "Disk full.".to_string(),
+        io::ErrorKind::QuotaExceeded => "Disk quota exceeded.".to_string(),
+        io::ErrorKind::IsADirectory => "That is a directory.".to_string(),
+        io::ErrorKind::NotADirectory => "Part of the path is not a directory.".to_string(),
+        io::ErrorKind::InvalidFilename => "The file name is not valid.".to_string(),
+        _ => err.to_string(),
+    }
+}
+
+fn closing_pair(c: char) -> Option<char> {
+    match c {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '"' => Some('"'),
+        _ => None,
+    }
+}
+
+// Indents every line after the first like the line the snippet expands on,
+// with the snippet's own leading tabs turned into the buffer's indent unit.
+// Placeholders are dropped; the offset of the first one is returned.
+fn fill_snippet(body: &str, indent: &str, unit: &str) -> (String, Option<usize>) {
+    let mut text = String::new();
+    let mut stop = None;
+    for (index, line) in body.split('\n').enumerate() {
+        if index > 0 {
+            text.push('\n');
+            text.push_str(indent);
+        }
+        let rest = line.trim_start_matches('\t');
+        for _ in 0..line.len().saturating_sub(rest.len()) {
+            text.push_str(unit);
+        }
+        let mut rest = rest;
+        while let Some((head, tail)) = rest.split_once("${") {
+            text.push_str(head);
+            stop = stop.or(Some(text.len()));
+            rest = tail.split_once('}').map_or("", |(_, tail)| tail);
+        }
+        text.push_str(rest);
+    }
+    (text, stop)
+}
+
+// Joins `name` onto `cwd` and canonicalizes its directory, so different
+// spellings of a path compare equal even before the file itself exists.
+fn resolve_path(name: &str, cwd: &Path) -> PathBuf {
+    let path = cwd.join(name);
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(file_name)) => {
+            fs::canonicalize(parent).map_or_else(|_| path.clone(), |parent| parent.join(file_name))
+        }
+        _ => path,
+    }
+}
+
+// Saving over a file that exists is only worth asking about when it isn't
+// the file being edited.
+fn replaces_other_file(
+    target: &Path,
+    current: Option<&Path>,
+    exists: impl Fn(&Path) -> bool,
+) -> bool {
+    exists(target) && current != Some(target)
+}
+
+// Splits a trailing `:line` or `:line:column` off a command line argument,
+// unless the whole argument names an existing file. Only all-digit parts
+// count, so drive letters like `C:\` stay part of the path.
+fn parse_location(arg: &str) -> (&str, Option<usize>, Option<usize>) {
+    if Path::new(arg).exists() {
+        return (arg, None, None);
+    }
+    let number = |part: &str| -> Option<usize> {
+        if part.is_empty() || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        part.parse().ok()
+    };
+    if let Some((rest, column)) = arg.rsplit_once(':') {
+        if let (Some(column), Some((path, line))) = (number(column), rest.rsplit_once(':')) {
+            if let Some(line) = number(line).filter(|_| !path.is_empty()) {
+                return (path, Some(line), Some(column));
+            }
+        }
+        if let Some(line) = number(column).filter(|_| !rest.is_empty()) {
+            return (rest, Some(line), None);
+        }
+    }
+    (arg, None, None)
 }
 
 fn die(e: std::io::Error) -> ! {
-    Terminal::clear_screen();
-    panic!("{e:?}");
+    panic!("I/O error: {e:?}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locations_on_the_command_line() {
+        assert_eq!(parse_location("src/main.rs"), ("src/main.rs", None, None));
+        assert_eq!(
+            parse_location("src/main.rs:42"),
+            ("src/main.rs", Some(42), None)
+        );
+        assert_eq!(
+            parse_location("src/main.rs:42:10"),
+            ("src/main.rs", Some(42), Some(10))
+        );
+        assert_eq!(parse_location(r"C:\x.rs:3"), (r"C:\x.rs", Some(3), None));
+        assert_eq!(parse_location(r"C:\x.rs"), (r"C:\x.rs", None, None));
+        assert_eq!(parse_location("a:b"), ("a:b", None, None));
+        assert_eq!(parse_location(":42"), (":42", None, None));
+    }
+
+    #[test]
+    fn overwriting_asks_only_for_other_existing_files() {
+        let exists = |path: &Path| path == Path::new("/work/a.txt");
+        let target = Path::new("/work/a.txt");
+        assert!(replaces_other_file(target, None, exists));
+        assert!(replaces_other_file(
+            target,
+            Some(Path::new("/work/b.txt")),
+            exists
+        ));
+        assert!(!replaces_other_file(target, Some(target), exists));
+        assert!(!replaces_other_file(
+            Path::new("/work/new.txt"),
+            None,
+            exists
+        ));
+    }
+
+    #[test]
+    fn paths_resolve_against_the_working_directory() {
+        let cwd = fs::canonicalize(env::temp_dir()).unwrap();
+        assert_eq!(resolve_path("a.txt", &cwd), cwd.join("a.txt"));
+        assert_eq!(resolve_path("./a.txt", &cwd), cwd.join("a.txt"));
+        let name = cwd.join("a.txt");
+        let name = name.to_str().unwrap();
+        assert_eq!(
+            resolve_path(name, Path::new("/elsewhere")),
+            cwd.join("a.txt")
+        );
+        assert_eq!(
+            resolve_path("missing/a.txt", &cwd),
+            cwd.join("missing/a.txt")
+        );
+    }
+
+    #[test]
+    fn a_resize_redraws_the_whole_screen() {
+        let capture = crate::Capture::default();
+        let size = Size {
+            width: 80,
+            height: 10,
+        };
+        let mut document = Document::default();
+        let text: Vec<String> = (0..20).map(|line| format!("line {line}")).collect();
+        document
+            .insert_text(&Position::default(), &text.join("\n"))
+            .unwrap();
+        let terminal = Terminal::scripted(size, Vec::new(), capture.clone());
+        let mut editor = EditorBuilder::new()
+            .terminal(terminal)
+            .document(document)
+            .build();
+        editor.refresh_screen().unwrap();
+        let drawn = capture.contents().len();
+        // The rows look the same at the new width, but the screen was
+        // rearranged by the terminal and has to be drawn again.
+        editor.terminal.resize(Size {
+            width: 40,
+            height: 10,
+        });
+        editor.refresh_screen().unwrap();
+        let redrawn = &capture.contents()[drawn..];
+        assert!(redrawn.contains(&format!("{}", termion::clear::All)));
+        assert!((0..8).all(|line| redrawn.contains(&format!("line {line}"))));
+    }
+
+    #[test]
+    fn digits_are_typed_while_recent_files_are_listed() {
+        let path = std::env::temp_dir().join(format!("rtext-welcome-{}", std::process::id()));
+        fs::write(&path, "recent\n").unwrap();
+        let path = path.to_str().unwrap().to_string();
+        let welcome = |keys| {
+            let terminal = Terminal::scripted(
+                Size {
+                    width: 80,
+                    height: 10,
+                },
+                keys,
+                crate::Capture::default(),
+            );
+            let mut editor = EditorBuilder::new().terminal(terminal).build();
+            editor.recent_files.add(&path);
+            editor.welcome_selection = Some(0);
+            editor.run();
+            editor
+        };
+        let editor = welcome(vec![Key::Char('1'), Key::Char('2')]);
+        assert!(editor.document.file_name.is_none());
+        assert_eq!(editor.document.row(0).unwrap().as_str(), "12");
+        let editor = welcome(vec![Key::Alt('1')]);
+        assert!(editor.document.file_name.is_some());
+        assert_eq!(editor.document.row(0).unwrap().as_str(), "recent");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn search_and_replace_stay_inside_the_selection() {
+        let selected = |keys: &str| {
+            let capture = crate::Capture::default();
+            let mut document = Document::default();
+            document
+                .insert_text(&Position::default(), "a a\na a\na a")
+                .unwrap();
+            let terminal = Terminal::scripted(
+                Size {
+                    width: 80,
+                    height: 10,
+                },
+                keys.chars().map(Key::Char).collect(),
+                capture.clone(),
+            );
+            let mut editor = EditorBuilder::new()
+                .terminal(terminal)
+                .document(document)
+                .build();
+            editor.selection_start = Some(Position { x: 2, y: 0 });
+            editor.cursor_position = Position { x: 1, y: 1 };
+            (editor, capture)
+        };
+        let (mut editor, capture) = selected("a\nb\n");
+        editor.replace_all();
+        assert!(capture.contents().contains("(in selection)"));
+        let rows: Vec<&str> = (0..3)
+            .filter_map(|y| editor.document.row(y).map(Row::as_str))
+            .collect();
+        assert_eq!(rows, vec!["a b", "b a", "a a"]);
+        assert!(editor.selection_start.is_none());
+
+        let (mut editor, _) = selected("a\n");
+        editor.cursor_position = Position { x: 0, y: 2 };
+        editor.selection_start = Some(Position { x: 1, y: 0 });
+        editor.search();
+        assert_eq!(editor.cursor_position, Position { x: 2, y: 0 });
+    }
 }
@@ -1,3 +1,45 @@
+use crate::highlighting::Highlighter;
+use std::path::Path;
+
+#[derive(Debug)]
+enum Pattern {
+    Extension(&'static str),
+    Name(&'static str),
+}
+
+const PATTERNS: &[(Pattern, &str)] = &[
+    (Pattern::Extension("rs"), "Rust"),
+    (Pattern::Extension("toml"), "TOML"),
+    (Pattern::Name("Cargo.lock"), "TOML"),
+    (Pattern::Extension("ini"), "INI"),
+    (Pattern::Name(".editorconfig"), "INI"),
+    (Pattern::Name(".gitconfig"), "INI"),
+    (Pattern::Extension("c"), "C"),
+    (Pattern::Extension("h"), "C"),
+    (Pattern::Extension("css"), "CSS"),
+    (Pattern::Extension("lua"), "Lua"),
+    (Pattern::Extension("py"), "Python"),
+    (Pattern::Extension("pyi"), "Python"),
+    (Pattern::Extension("pyw"), "Python"),
+    (Pattern::Extension("sh"), "Shell"),
+    (Pattern::Extension("bash"), "Shell"),
+    (Pattern::Name(".bashrc"), "Shell"),
+    (Pattern::Name(".bash_profile"), "Shell"),
+    (Pattern::Name(".profile"), "Shell"),
+    (Pattern::Name(".zshrc"), "Shell"),
+    (Pattern::Extension("md"), "Markdown"),
+    (Pattern::Extension("markdown"), "Markdown"),
+    (Pattern::Extension("txt"), "Text"),
+    (Pattern::Name("Makefile"), "Makefile"),
+    (Pattern::Name("makefile"), "Makefile"),
+    (Pattern::Name("GNUmakefile"), "Makefile"),
+    (Pattern::Extension("mk"), "Makefile"),
+    (Pattern::Name("Dockerfile"), "Dockerfile"),
+    (Pattern::Name("Containerfile"), "Dockerfile"),
+    (Pattern::Name(".gitignore"), "Ignore"),
+    (Pattern::Name(".dockerignore"), "Ignore"),
+];
+
 #[derive(Debug)]
 pub struct FileType {
     name: String,
@@ -10,7 +52,17 @@ pub struct HighlightingOptions {
     strings: bool,
     characters: bool,
     comments: bool,
+    comment_starts: Vec<String>,
     multiline_comments: bool,
+    single_quoted_strings: bool,
+    literal_single_quotes: bool,
+    raw_strings: bool,
+    sections: bool,
+    keys: bool,
+    variables: bool,
+    spell_check: bool,
+    brackets: bool,
+    word_chars: Vec<char>,
     primary_keywords: Vec<String>,
     secondary_keywords: Vec<String>,
 }
@@ -32,10 +84,56 @@ impl HighlightingOptions {
         self.comments
     }
 
+    pub fn comment_starts(&self) -> &Vec<String> {
+        &self.comment_starts
+    }
+
     pub fn multiline_comments(&self) -> bool {
         self.multiline_comments
     }
 
+    pub fn single_quoted_strings(&self) -> bool {
+        self.single_quoted_strings
+    }
+
+    // Whether a backslash escapes the next character in strings quoted with
+    // `quote`. Single-quoted strings in the shell and TOML are literal.
+    pub fn escapes_in(&self, quote: char) -> bool {
+        quote == '"' || !self.literal_single_quotes
+    }
+
+    pub fn raw_strings(&self) -> bool {
+        self.raw_strings
+    }
+
+    pub fn sections(&self) -> bool {
+        self.sections
+    }
+
+    pub fn keys(&self) -> bool {
+        self.keys
+    }
+
+    pub fn variables(&self) -> bool {
+        self.variables
+    }
+
+    pub fn spell_check(&self) -> bool {
+        self.spell_check
+    }
+
+    pub fn brackets(&self) -> bool {
+        self.brackets
+    }
+
+    pub fn word_chars(&self) -> &Vec<char> {
+        &self.word_chars
+    }
+
+    pub fn is_word_char(&self, c: char) -> bool {
+        c.is_alphanumeric() || c == '_' || self.word_chars.contains(&c)
+    }
+
     pub fn primary_keywords(&self) -> &Vec<String> {
         &self.primary_keywords
     }
@@ -54,89 +152,439 @@ impl Default for FileType {
 }
 
 impl FileType {
+    // Whole file names are tried before extensions. Extensions match
+    // case-insensitively, names exactly.
     pub fn from(file_name: &str) -> Self {
-        if file_name.ends_with(".rs") {
-            return Self {
-                name: String::from("Rust"),
-                hl_opts: HighlightingOptions {
-                    numbers: true,
-                    strings: true,
-                    characters: true,
-                    comments: true,
-                    multiline_comments: true,
-                    primary_keywords: vec![
-                        "as".to_string(),
-                        "break".to_string(),
-                        "const".to_string(),
-                        "continue".to_string(),
-                        "crate".to_string(),
-                        "else".to_string(),
-                        "enum".to_string(),
-                        "extern".to_string(),
-                        "false".to_string(),
-                        "fn".to_string(),
-                        "for".to_string(),
-                        "if".to_string(),
-                        "impl".to_string(),
-                        "in".to_string(),
-                        "let".to_string(),
-                        "loop".to_string(),
-                        "match".to_string(),
-                        "mod".to_string(),
-                        "move".to_string(),
-                        "mut".to_string(),
-                        "pub".to_string(),
-                        "ref".to_string(),
-                        "return".to_string(),
-                        "self".to_string(),
-                        "Self".to_string(),
-                        "static".to_string(),
-                        "struct".to_string(),
-                        "super".to_string(),
-                        "trait".to_string(),
-                        "true".to_string(),
-                        "type".to_string(),
-                        "unsafe".to_string(),
-                        "use".to_string(),
-                        "where".to_string(),
-                        "while".to_string(),
-                        "dyn".to_string(),
-                        "abstract".to_string(),
-                        "become".to_string(),
-                        "box".to_string(),
-                        "do".to_string(),
-                        "final".to_string(),
-                        "macro".to_string(),
-                        "override".to_string(),
-                        "priv".to_string(),
-                        "typeof".to_string(),
-                        "unsized".to_string(),
-                        "virtual".to_string(),
-                        "yield".to_string(),
-                        "async".to_string(),
-                        "await".to_string(),
-                        "try".to_string(),
-                    ],
-                    secondary_keywords: vec![
-                        "bool".to_string(),
-                        "char".to_string(),
-                        "i8".to_string(),
-                        "i16".to_string(),
-                        "i32".to_string(),
-                        "i64".to_string(),
-                        "isize".to_string(),
-                        "u8".to_string(),
-                        "u16".to_string(),
-                        "u32".to_string(),
-                        "u64".to_string(),
-                        "usize".to_string(),
-                        "f32".to_string(),
-                        "f64".to_string(),
-                    ],
-                },
-            };
+        let base = file_name.rsplit('/').next().unwrap_or(file_name);
+        let extension = Path::new(base)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let by_name = PATTERNS
+            .iter()
+            .find(|(pattern, _)| matches!(pattern, Pattern::Name(name) if *name == base));
+        let by_extension = || {
+            PATTERNS.iter().find(|(pattern, _)| {
+                matches!(pattern, Pattern::Extension(ext) if Some(*ext) == extension.as_deref())
+            })
+        };
+        by_name
+            .or_else(by_extension)
+            .and_then(|(_, name)| Self::from_name(name))
+            .unwrap_or_default()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::rust(),
+            Self::config("TOML"),
+            Self::config("INI"),
+            Self::c(),
+            Self::css(),
+            Self::lua(),
+            Self::python(),
+            Self::shell(),
+            Self::prose("Markdown"),
+            Self::prose("Text"),
+            Self::hash_comments("Makefile"),
+            Self::hash_comments("Dockerfile"),
+            Self::hash_comments("Ignore"),
+        ]
+        .into_iter()
+        .find(|file_type| file_type.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn from_shebang(line: &str) -> Option<Self> {
+        let mut command = line.strip_prefix("#!")?.split_whitespace();
+        let mut interpreter = command.next()?.rsplit('/').next()?;
+        if interpreter == "env" {
+            interpreter = command.find(|arg| !arg.starts_with('-'))?;
+        }
+        match interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+            "sh" | "bash" | "zsh" | "ksh" | "dash" => Some(Self::shell()),
+            "python" | "pypy" => Some(Self::python()),
+            "lua" | "luajit" => Some(Self::lua()),
+            _ => None,
+        }
+    }
+
+    // Only the very first line can name a script's interpreter.
+    pub fn from_content(first_lines: &[&str]) -> Option<Self> {
+        first_lines
+            .first()
+            .and_then(|line| Self::from_shebang(line))
+    }
+
+    pub fn detect(file_name: &str, first_line: Option<&str>) -> Self {
+        Self::from_content(first_line.as_slice()).unwrap_or_else(|| Self::from(file_name))
+    }
+
+    fn rust() -> Self {
+        Self {
+            name: String::from("Rust"),
+            hl_opts: HighlightingOptions {
+                numbers: true,
+                strings: true,
+                characters: true,
+                comments: true,
+                comment_starts: vec!["//".to_string()],
+                multiline_comments: true,
+                raw_strings: true,
+                brackets: true,
+                primary_keywords: vec![
+                    "as".to_string(),
+                    "break".to_string(),
+                    "const".to_string(),
+                    "continue".to_string(),
+                    "crate".to_string(),
+                    "else".to_string(),
+                    "enum".to_string(),
+                    "extern".to_string(),
+                    "false".to_string(),
+                    "fn".to_string(),
+                    "for".to_string(),
+                    "if".to_string(),
+                    "impl".to_string(),
+                    "in".to_string(),
+                    "let".to_string(),
+                    "loop".to_string(),
+                    "match".to_string(),
+                    "mod".to_string(),
+                    "move".to_string(),
+                    "mut".to_string(),
+                    "pub".to_string(),
+                    "ref".to_string(),
+                    "return".to_string(),
+                    "self".to_string(),
+                    "Self".to_string(),
+                    "static".to_string(),
+                    "struct".to_string(),
+                    "super".to_string(),
+                    "trait".to_string(),
+                    "true".to_string(),
+                    "type".to_string(),
+                    "unsafe".to_string(),
+                    "use".to_string(),
+                    "where".to_string(),
+                    "while".to_string(),
+                    "dyn".to_string(),
+                    "abstract".to_string(),
+                    "become".to_string(),
+                    "box".to_string(),
+                    "do".to_string(),
+                    "final".to_string(),
+                    "macro".to_string(),
+                    "override".to_string(),
+                    "priv".to_string(),
+                    "typeof".to_string(),
+                    "unsized".to_string(),
+                    "virtual".to_string(),
+                    "yield".to_string(),
+                    "async".to_string(),
+                    "await".to_string(),
+                    "try".to_string(),
+                ],
+                secondary_keywords: vec![
+                    "bool".to_string(),
+                    "char".to_string(),
+                    "i8".to_string(),
+                    "i16".to_string(),
+                    "i32".to_string(),
+                    "i64".to_string(),
+                    "isize".to_string(),
+                    "u8".to_string(),
+                    "u16".to_string(),
+                    "u32".to_string(),
+                    "u64".to_string(),
+                    "usize".to_string(),
+                    "f32".to_string(),
+                    "f64".to_string(),
+                ],
+                ..HighlightingOptions::default()
+            },
+        }
+    }
+
+    fn c() -> Self {
+        Self {
+            name: String::from("C"),
+            hl_opts: HighlightingOptions {
+                numbers: true,
+                strings: true,
+                characters: true,
+                comments: true,
+                comment_starts: vec!["//".to_string()],
+                multiline_comments: true,
+                brackets: true,
+                primary_keywords: vec![
+                    "auto".to_string(),
+                    "break".to_string(),
+                    "case".to_string(),
+                    "const".to_string(),
+                    "continue".to_string(),
+                    "default".to_string(),
+                    "do".to_string(),
+                    "else".to_string(),
+                    "enum".to_string(),
+                    "extern".to_string(),
+                    "for".to_string(),
+                    "goto".to_string(),
+                    "if".to_string(),
+                    "inline".to_string(),
+                    "register".to_string(),
+                    "restrict".to_string(),
+                    "return".to_string(),
+                    "sizeof".to_string(),
+                    "static".to_string(),
+                    "struct".to_string(),
+                    "switch".to_string(),
+                    "typedef".to_string(),
+                    "union".to_string(),
+                    "volatile".to_string(),
+                    "while".to_string(),
+                ],
+                secondary_keywords: vec![
+                    "bool".to_string(),
+                    "char".to_string(),
+                    "double".to_string(),
+                    "float".to_string(),
+                    "int".to_string(),
+                    "long".to_string(),
+                    "short".to_string(),
+                    "signed".to_string(),
+                    "size_t".to_string(),
+                    "unsigned".to_string(),
+                    "void".to_string(),
+                ],
+                ..HighlightingOptions::default()
+            },
+        }
+    }
+
+    fn config(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            hl_opts: HighlightingOptions {
+                numbers: true,
+                strings: true,
+                comments: true,
+                comment_starts: vec!["#".to_string(), ";".to_string()],
+                single_quoted_strings: true,
+                literal_single_quotes: true,
+                sections: true,
+                keys: true,
+                brackets: true,
+                ..HighlightingOptions::default()
+            },
+        }
+    }
+
+    fn css() -> Self {
+        Self {
+            name: String::from("CSS"),
+            hl_opts: HighlightingOptions {
+                numbers: true,
+                strings: true,
+                multiline_comments: true,
+                single_quoted_strings: true,
+                brackets: true,
+                word_chars: vec!['-'],
+                primary_keywords: vec![
+                    "@import".to_string(),
+                    "@media".to_string(),
+                    "@font-face".to_string(),
+                    "@keyframes".to_string(),
+                    "@supports".to_string(),
+                    "!important".to_string(),
+                ],
+                ..HighlightingOptions::default()
+            },
+        }
+    }
+
+    // Formats with little more than `#` comments and `$` variables.
+    fn hash_comments(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            hl_opts: HighlightingOptions {
+                numbers: true,
+                strings: true,
+                comments: true,
+                comment_starts: vec!["#".to_string()],
+                variables: true,
+                ..HighlightingOptions::default()
+            },
+        }
+    }
+
+    fn lua() -> Self {
+        Self {
+            name: String::from("Lua"),
+            hl_opts: HighlightingOptions {
+                numbers: true,
+                strings: true,
+                comments: true,
+                comment_starts: vec!["--".to_string()],
+                single_quoted_strings: true,
+                brackets: true,
+                primary_keywords: vec![
+                    "and".to_string(),
+                    "break".to_string(),
+                    "do".to_string(),
+                    "else".to_string(),
+                    "elseif".to_string(),
+                    "end".to_string(),
+                    "false".to_string(),
+                    "for".to_string(),
+                    "function".to_string(),
+                    "goto".to_string(),
+                    "if".to_string(),
+                    "in".to_string(),
+                    "local".to_string(),
+                    "nil".to_string(),
+                    "not".to_string(),
+                    "or".to_string(),
+                    "repeat".to_string(),
+                    "return".to_string(),
+                    "then".to_string(),
+                    "true".to_string(),
+                    "until".to_string(),
+                    "while".to_string(),
+                ],
+                ..HighlightingOptions::default()
+            },
+        }
+    }
+
+    fn prose(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            hl_opts: HighlightingOptions {
+                spell_check: true,
+                ..HighlightingOptions::default()
+            },
+        }
+    }
+
+    fn python() -> Self {
+        Self {
+            name: String::from("Python"),
+            hl_opts: HighlightingOptions {
+                numbers: true,
+                strings: true,
+                comments: true,
+                comment_starts: vec!["#".to_string()],
+                single_quoted_strings: true,
+                brackets: true,
+                primary_keywords: vec![
+                    "and".to_string(),
+                    "as".to_string(),
+                    "assert".to_string(),
+                    "async".to_string(),
+                    "await".to_string(),
+                    "break".to_string(),
+                    "class".to_string(),
+                    "continue".to_string(),
+                    "def".to_string(),
+                    "del".to_string(),
+                    "elif".to_string(),
+                    "else".to_string(),
+                    "except".to_string(),
+                    "False".to_string(),
+                    "finally".to_string(),
+                    "for".to_string(),
+                    "from".to_string(),
+                    "global".to_string(),
+                    "if".to_string(),
+                    "import".to_string(),
+                    "in".to_string(),
+                    "is".to_string(),
+                    "lambda".to_string(),
+                    "None".to_string(),
+                    "nonlocal".to_string(),
+                    "not".to_string(),
+                    "or".to_string(),
+                    "pass".to_string(),
+                    "raise".to_string(),
+                    "return".to_string(),
+                    "True".to_string(),
+                    "try".to_string(),
+                    "while".to_string(),
+                    "with".to_string(),
+                    "yield".to_string(),
+                ],
+                secondary_keywords: vec![
+                    "bool".to_string(),
+                    "bytes".to_string(),
+                    "dict".to_string(),
+                    "float".to_string(),
+                    "int".to_string(),
+                    "list".to_string(),
+                    "object".to_string(),
+                    "self".to_string(),
+                    "set".to_string(),
+                    "str".to_string(),
+                    "tuple".to_string(),
+                    "type".to_string(),
+                ],
+                ..HighlightingOptions::default()
+            },
+        }
+    }
+
+    fn shell() -> Self {
+        Self {
+            name: String::from("Shell"),
+            hl_opts: HighlightingOptions {
+                numbers: true,
+                strings: true,
+                comments: true,
+                comment_starts: vec!["#".to_string()],
+                single_quoted_strings: true,
+                literal_single_quotes: true,
+                variables: true,
+                primary_keywords: vec![
+                    "if".to_string(),
+                    "then".to_string(),
+                    "else".to_string(),
+                    "elif".to_string(),
+                    "fi".to_string(),
+                    "for".to_string(),
+                    "while".to_string(),
+                    "until".to_string(),
+                    "do".to_string(),
+                    "done".to_string(),
+                    "case".to_string(),
+                    "esac".to_string(),
+                    "in".to_string(),
+                    "function".to_string(),
+                    "select".to_string(),
+                    "return".to_string(),
+                    "break".to_string(),
+                    "continue".to_string(),
+                ],
+                secondary_keywords: vec![
+                    "echo".to_string(),
+                    "printf".to_string(),
+                    "read".to_string(),
+                    "export".to_string(),
+                    "local".to_string(),
+                    "readonly".to_string(),
+                    "declare".to_string(),
+                    "unset".to_string(),
+                    "set".to_string(),
+                    "shift".to_string(),
+                    "source".to_string(),
+                    "eval".to_string(),
+                    "exec".to_string(),
+                    "exit".to_string(),
+                    "trap".to_string(),
+                    "cd".to_string(),
+                    "test".to_string(),
+                ],
+                ..HighlightingOptions::default()
+            },
         }
-        Self::default()
     }
 
     pub fn name(&self) -> String {
@@ -146,4 +594,8 @@ impl FileType {
     pub fn highlighting_options(&self) -> &HighlightingOptions {
         &self.hl_opts
     }
+
+    pub fn highlighter(&self) -> &dyn Highlighter {
+        &self.hl_opts
+    }
 }
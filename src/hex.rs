@@ -0,0 +1,86 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+pub const BYTES_PER_ROW: usize = 16;
+
+#[derive(Debug)]
+pub struct HexView {
+    path: PathBuf,
+    file_len: u64,
+    pub row: usize,
+    pub offset: usize,
+}
+
+impl HexView {
+    pub fn open(path: &Path) -> Result<Self, io::Error> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            file_len: path.metadata()?.len(),
+            row: 0,
+            offset: 0,
+        })
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn len(&self) -> usize {
+        let rows = self.file_len / BYTES_PER_ROW as u64;
+        let partial = u64::from(self.file_len % BYTES_PER_ROW as u64 > 0);
+        rows.saturating_add(partial) as usize
+    }
+
+    pub fn move_to(&mut self, row: usize, height: usize) {
+        self.row = row.min(self.len().saturating_sub(1));
+        let height = height.max(1);
+        if self.row < self.offset {
+            self.offset = self.row;
+        } else if self.row >= self.offset.saturating_add(height) {
+            self.offset = self.row.saturating_sub(height).saturating_add(1);
+        }
+    }
+
+    pub fn render(&self, height: usize) -> Result<Vec<String>, io::Error> {
+        let start = self.offset.saturating_mul(BYTES_PER_ROW);
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(start as u64))?;
+        let mut bytes = Vec::new();
+        file.take(height.saturating_mul(BYTES_PER_ROW) as u64)
+            .read_to_end(&mut bytes)?;
+        Ok(bytes
+            .chunks(BYTES_PER_ROW)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let address = index
+                    .saturating_add(self.offset)
+                    .saturating_mul(BYTES_PER_ROW);
+                render_row(address, chunk)
+            })
+            .collect())
+    }
+}
+
+fn render_row(address: usize, chunk: &[u8]) -> String {
+    let mut hex = String::new();
+    for index in 0..BYTES_PER_ROW {
+        if index == BYTES_PER_ROW / 2 {
+            hex.push(' ');
+        }
+        match chunk.get(index) {
+            Some(byte) => hex.push_str(&format!("{byte:02x} ")),
+            None => hex.push_str("   "),
+        }
+    }
+    let ascii: String = chunk
+        .iter()
+        .map(|byte| {
+            if byte.is_ascii_graphic() || *byte == b' ' {
+                char::from(*byte)
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    format!("{address:08x}  {hex} |{ascii}|")
+}
@@ -11,10 +11,22 @@ pub enum Type {
     MultilineComment,
     PrimaryKeywords,
     SecondaryKeywords,
+    Section,
+    Key,
+    Variable,
+    Misspelled,
+    MatchQuote,
+    BracketError,
+    ControlChar,
+    Url,
 }
 
 impl Type {
     pub fn to_color(self) -> impl color::Color {
+        self.to_rgb()
+    }
+
+    pub fn to_rgb(self) -> color::Rgb {
         match self {
             Type::Number => color::Rgb(220, 163, 163),
             Type::Match => color::Rgb(38, 139, 210),
@@ -23,7 +35,31 @@ impl Type {
             Type::Comment | Type::MultilineComment => color::Rgb(133, 153, 0),
             Type::PrimaryKeywords => color::Rgb(181, 137, 0),
             Type::SecondaryKeywords => color::Rgb(42, 161, 152),
+            Type::Section => color::Rgb(203, 75, 22),
+            Type::Key => color::Rgb(108, 153, 187),
+            Type::Variable => color::Rgb(220, 50, 47),
+            Type::Misspelled => color::Rgb(255, 85, 85),
+            Type::MatchQuote => color::Rgb(253, 246, 227),
+            Type::BracketError => color::Rgb(255, 0, 0),
+            Type::ControlChar => color::Rgb(147, 161, 161),
+            Type::Url => color::Rgb(102, 153, 255),
             _ => color::Rgb(255, 255, 255),
         }
     }
 }
+
+// What a row leaves open for the row after it to continue.
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub enum Continuation {
+    #[default]
+    None,
+    Comment,
+    RawString(usize),
+}
+
+// Turns the text of one row into a highlighting type per char. `start` is
+// what the previous row left open and the returned continuation is what this
+// one leaves open, so the document can thread it from row to row.
+pub trait Highlighter {
+    fn highlight_row(&self, text: &str, start: Continuation) -> (Vec<Type>, Continuation);
+}
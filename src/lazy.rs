@@ -0,0 +1,175 @@
+use crate::Row;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+pub const LAZY_THRESHOLD: u64 = 64 * 1024 * 1024;
+const WINDOW_MARGIN: usize = 256;
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+const PROGRESS_INTERVAL: u64 = 16 * READ_CHUNK_SIZE as u64;
+
+#[derive(Debug)]
+pub struct LazyLines {
+    path: PathBuf,
+    line_starts: Vec<u64>,
+    file_len: u64,
+    window_start: usize,
+    window_end: usize,
+}
+
+impl LazyLines {
+    pub fn open(path: &Path, progress: &mut impl FnMut(usize)) -> Result<Self, io::Error> {
+        let mut lines = Self {
+            path: path.to_path_buf(),
+            line_starts: vec![0],
+            file_len: 0,
+            window_start: 0,
+            window_end: 0,
+        };
+        lines.index(File::open(path)?, progress)?;
+        Ok(lines)
+    }
+
+    // Indexes lines appended since the file was last read, starting over if
+    // the file shrank. Returns whether anything changed.
+    pub fn refresh(&mut self) -> Result<bool, io::Error> {
+        let len = fs::metadata(&self.path)?.len();
+        if len == self.file_len {
+            return Ok(false);
+        }
+        if len < self.file_len {
+            self.line_starts = vec![0];
+            self.file_len = 0;
+        }
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.file_len))?;
+        self.index(file, &mut |_| {})?;
+        // The last loaded row may have grown, so reload the window.
+        self.window_end = 0;
+        Ok(true)
+    }
+
+    // A final newline leaves a line start at the very end of the file, which
+    // only becomes a row once more text is appended.
+    pub fn len(&self) -> usize {
+        let len = self.line_starts.len();
+        if self.line_starts.last() == Some(&self.file_len) {
+            len.saturating_sub(1)
+        } else {
+            len
+        }
+    }
+
+    // Reports progress each time another interval has been read, however
+    // the reads happen to line up with it.
+    fn index(
+        &mut self,
+        file: impl Read,
+        progress: &mut impl FnMut(usize),
+    ) -> Result<(), io::Error> {
+        let mut reader = BufReader::new(file);
+        let mut chunk = vec![0; READ_CHUNK_SIZE];
+        let mut reported = self.file_len;
+        loop {
+            let read = reader.read(&mut chunk)?;
+            if read == 0 {
+                break;
+            }
+            for (index, byte) in chunk.iter().take(read).enumerate() {
+                if *byte == b'\n' {
+                    self.line_starts
+                        .push(self.file_len.saturating_add(index as u64).saturating_add(1));
+                }
+            }
+            self.file_len = self.file_len.saturating_add(read as u64);
+            if self.file_len.saturating_sub(reported) >= PROGRESS_INTERVAL {
+                progress(self.line_starts.len());
+                reported = self.file_len;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn window_start(&self) -> usize {
+        self.window_start
+    }
+
+    pub fn contains(&self, start: usize, end: usize) -> bool {
+        start >= self.window_start && end <= self.window_end
+    }
+
+    pub fn load(&mut self, start: usize, end: usize) -> Result<Vec<Row>, io::Error> {
+        let start = start.saturating_sub(WINDOW_MARGIN);
+        let end = end.saturating_add(WINDOW_MARGIN).min(self.len());
+        let rows = self.read_rows(start, end)?;
+        self.window_start = start;
+        self.window_end = end;
+        Ok(rows)
+    }
+
+    pub fn read_row(&self, index: usize) -> Option<Row> {
+        self.read_rows(index, index.saturating_add(1))
+            .ok()
+            .and_then(|mut rows| rows.pop())
+    }
+
+    fn read_rows(&self, start: usize, end: usize) -> Result<Vec<Row>, io::Error> {
+        let from = self
+            .line_starts
+            .get(start)
+            .copied()
+            .unwrap_or(self.file_len);
+        let to = self.line_starts.get(end).copied().unwrap_or(self.file_len);
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(from))?;
+        let mut bytes = Vec::new();
+        file.take(to.saturating_sub(from)).read_to_end(&mut bytes)?;
+        Ok(String::from_utf8_lossy(&bytes)
+            .lines()
+            .map(Row::from)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hands out one byte first, so no later read ends on a multiple of the
+    // chunk size.
+    struct ShortFirstRead {
+        remaining: usize,
+        first: bool,
+    }
+
+    impl Read for ShortFirstRead {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = if self.first { 1 } else { buf.len() }.min(self.remaining);
+            self.first = false;
+            self.remaining -= len;
+            buf[..len].fill(b'\n');
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn progress_survives_a_short_read() {
+        let mut lines = LazyLines {
+            path: PathBuf::new(),
+            line_starts: vec![0],
+            file_len: 0,
+            window_start: 0,
+            window_end: 0,
+        };
+        let file = ShortFirstRead {
+            remaining: 4 * PROGRESS_INTERVAL as usize,
+            first: true,
+        };
+        let mut reports = Vec::new();
+        lines.index(file, &mut |lines| reports.push(lines)).unwrap();
+        assert_eq!(reports.len(), 3);
+        assert!(reports.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
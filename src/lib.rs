@@ -0,0 +1,46 @@
+#![warn(clippy::all, clippy::pedantic, clippy::restriction)]
+#![allow(
+    clippy::missing_docs_in_private_items,
+    clippy::implicit_return,
+    clippy::shadow_reuse,
+    clippy::print_stdout,
+    clippy::wildcard_enum_match_arm,
+    clippy::else_if_without_else
+)]
+mod brackets;
+mod clipboard;
+mod config;
+mod crash;
+mod diff;
+mod document;
+mod editor;
+mod filetype;
+mod filter;
+mod hex;
+mod highlighting;
+mod lazy;
+mod recent;
+mod row;
+mod spelling;
+mod swap;
+mod syntax;
+mod terminal;
+
+pub use config::Config;
+pub use document::Document;
+pub use document::Encoding;
+pub use editor::Editor;
+pub use editor::EditorBuilder;
+pub use editor::Position;
+pub use editor::SearchDirection;
+pub use filetype::FileType;
+pub use filetype::HighlightingOptions;
+pub use highlighting::Continuation;
+pub use highlighting::Highlighter;
+pub use highlighting::Type as HighlightType;
+pub use row::Row;
+pub use spelling::Dictionary;
+pub use terminal::Capture;
+pub use terminal::CursorShape;
+pub use terminal::Size;
+pub use terminal::Terminal;
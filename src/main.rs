@@ -7,23 +7,16 @@
     clippy::wildcard_enum_match_arm,
     clippy::else_if_without_else
 )]
-mod document;
-mod editor;
-mod filetype;
-mod highlighting;
-mod row;
-mod terminal;
-
-pub use document::Document;
-use editor::Editor;
-pub use editor::Position;
-pub use editor::SearchDirection;
-pub use filetype::FileType;
-pub use filetype::HighlightingOptions;
-pub use row::Row;
-pub use terminal::Terminal;
+use rtext::Editor;
+use std::env;
 
 fn main() {
-    let mut editor = Editor::new();
-    editor.run();
+    match env::args().nth(1).as_deref() {
+        Some("--version" | "-V") => println!("{}", Editor::version()),
+        Some("--help" | "-h") => print!("{}", Editor::usage()),
+        _ => {
+            let mut editor = Editor::new();
+            editor.run();
+        }
+    }
 }
@@ -0,0 +1,55 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+const RECENT_FILE_NAME: &str = ".rtext_recent";
+pub const MAX_RECENT_FILES: usize = 9;
+
+#[derive(Default, Debug)]
+pub struct RecentFiles {
+    paths: Vec<String>,
+    state_file: Option<PathBuf>,
+}
+
+impl RecentFiles {
+    pub fn load() -> Self {
+        let state_file = env::var_os("HOME").map(|home| Path::new(&home).join(RECENT_FILE_NAME));
+        let paths = state_file
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| {
+                content
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .take(MAX_RECENT_FILES)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { paths, state_file }
+    }
+
+    pub fn existing(&self) -> Vec<&str> {
+        self.paths
+            .iter()
+            .filter(|path| Path::new(path).is_file())
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn add(&mut self, path: &str) {
+        let path = fs::canonicalize(path)
+            .ok()
+            .and_then(|path| path.to_str().map(str::to_string))
+            .unwrap_or_else(|| path.to_string());
+        self.paths.retain(|recent| *recent != path);
+        self.paths.insert(0, path);
+        self.paths.truncate(MAX_RECENT_FILES);
+        if let Some(state_file) = &self.state_file {
+            let mut content = self.paths.join("\n");
+            content.push('\n');
+            let _ = fs::write(state_file, content);
+        }
+    }
+}
@@ -1,16 +1,23 @@
+use crate::document::LineEnding;
 use crate::highlighting;
+use crate::highlighting::{Continuation, Highlighter};
+use crate::Dictionary;
 use crate::HighlightingOptions;
 use crate::SearchDirection;
-use std::cmp;
-use termion::color;
-use unicode_segmentation::UnicodeSegmentation;
+use termion::{color, style};
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
 
-#[derive(Default, Debug)]
+const CROSSHAIR_BG_COLOR: color::Rgb = color::Rgb(48, 48, 48);
+
+#[derive(Default, Debug, Clone)]
 pub struct Row {
     string: String,
     highlighting: Vec<highlighting::Type>,
     len: usize,
     pub is_highlighted: bool,
+    pub unmatched_brackets: Vec<usize>,
+    continuation: Continuation,
+    ending: Option<LineEnding>,
 }
 
 impl From<&str> for Row {
@@ -20,45 +27,124 @@ impl From<&str> for Row {
             highlighting: Vec::new(),
             is_highlighted: false,
             len: slice.graphemes(true).count(),
+            unmatched_brackets: Vec::new(),
+            continuation: Continuation::None,
+            ending: None,
         }
     }
 }
 
 impl Row {
-    pub fn render(&self, start: usize, end: usize) -> String {
-        let end = cmp::min(end, self.string.len());
-        let start = cmp::min(start, end);
+    // Renders the graphemes from `start` into at most `end - start` columns.
+    // Control characters take up the width of their caret notation. The
+    // `crosshair` column gets a faint background, past the end of the row too,
+    // and graphemes in the `selection` range are shown inverted.
+    #[allow(clippy::integer_arithmetic)]
+    pub fn render(
+        &self,
+        start: usize,
+        end: usize,
+        quotes: Option<(usize, usize)>,
+        crosshair: Option<usize>,
+        selection: Option<(usize, usize)>,
+    ) -> String {
+        let width = end.saturating_sub(start);
+        let mut columns = 0;
         let mut result = String::new();
         let mut current_highlighting = &highlighting::Type::None;
-        #[allow(clippy::integer_arithmetic)]
-        for (index, grapheme) in self.string[..]
-            .graphemes(true)
-            .enumerate()
-            .skip(start)
-            .take(end - start)
-        {
+        for (index, grapheme) in self.string[..].graphemes(true).enumerate().skip(start) {
             if let Some(c) = grapheme.chars().next() {
-                let highlighting_type = self
-                    .highlighting
-                    .get(index)
-                    .unwrap_or(&highlighting::Type::None);
+                let notation = control_notation(c);
+                let column = columns;
+                columns += notation.as_ref().map_or(1, |notation| notation.len());
+                if columns > width {
+                    break;
+                }
+                let highlighting_type = if notation.is_some() {
+                    &highlighting::Type::ControlChar
+                } else if quotes.map_or(false, |(open, close)| index == open || index == close) {
+                    &highlighting::Type::MatchQuote
+                } else if self.unmatched_brackets.contains(&index) {
+                    &highlighting::Type::BracketError
+                } else {
+                    self.highlighting
+                        .get(index)
+                        .unwrap_or(&highlighting::Type::None)
+                };
                 if highlighting_type != current_highlighting {
                     current_highlighting = highlighting_type;
                     let start_highlight =
                         format!("{}", termion::color::Fg(highlighting_type.to_color()));
                     result.push_str(&start_highlight[..]);
                 }
-                if c == '\t' {
+                let in_crosshair =
+                    crosshair.map_or(false, |crosshair| (column..columns).contains(&crosshair));
+                let selected = selection.map_or(false, |(from, to)| (from..to).contains(&index));
+                if in_crosshair {
+                    result.push_str(&format!("{}", color::Bg(CROSSHAIR_BG_COLOR)));
+                }
+                if selected {
+                    result.push_str(&format!("{}", style::Invert));
+                }
+                if let Some(notation) = notation {
+                    result.push_str(&notation);
+                } else if c == '\t' {
                     result.push_str(" ");
                 } else {
                     result.push(c);
                 }
+                if selected {
+                    result.push_str(&format!("{}", style::NoInvert));
+                }
+                if in_crosshair {
+                    result.push_str(&format!("{}", color::Bg(color::Reset)));
+                }
             }
         }
+        let padding = crosshair.filter(|crosshair| (columns..width).contains(crosshair));
+        if let Some(crosshair) = padding {
+            result.push_str(&" ".repeat(crosshair - columns));
+            result.push_str(&format!(
+                "{} {}",
+                color::Bg(CROSSHAIR_BG_COLOR),
+                color::Bg(color::Reset)
+            ));
+        }
         let end_highlight = format!("{}", termion::color::Fg(color::Reset));
         result.push_str(&end_highlight[..]);
         result
     }
+    pub fn columns(&self, start: usize, end: usize) -> usize {
+        self.string[..]
+            .graphemes(true)
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .map(|grapheme| {
+                grapheme
+                    .chars()
+                    .next()
+                    .and_then(control_notation)
+                    .map_or(1, |notation| notation.len())
+            })
+            .sum()
+    }
+    // The most common highlighting besides plain text, if the row has any.
+    pub fn dominant_highlighting(&self) -> Option<highlighting::Type> {
+        let mut counts: Vec<(highlighting::Type, usize)> = Vec::new();
+        for hl_type in &self.highlighting {
+            if *hl_type == highlighting::Type::None {
+                continue;
+            }
+            match counts.iter_mut().find(|(counted, _)| counted == hl_type) {
+                Some((_, count)) => *count = count.saturating_add(1),
+                None => counts.push((*hl_type, 1)),
+            }
+        }
+        counts
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(hl_type, _)| hl_type)
+    }
     pub fn len(&self) -> usize {
         self.len
     }
@@ -66,72 +152,181 @@ impl Row {
         self.len == 0
     }
     pub fn insert(&mut self, at: usize, c: char) {
-        if at >= self.len() {
-            self.string.push(c);
+        let byte_index = self.byte_index(at);
+        let before = self.string[..byte_index].chars().next_back();
+        let after = self.string[byte_index..].chars().next();
+        // Between ASCII other than '\r' an ASCII char is a grapheme of its
+        // own. Anything else, like a combining mark or a '\n' after a '\r',
+        // can merge with its neighbours or split them up, so the graphemes
+        // on either side are counted again.
+        let plain = |c: Option<char>| c.map_or(true, |c| c.is_ascii() && c != '\r');
+        if plain(before) && plain(Some(c)) && plain(after) {
+            self.string.insert(byte_index, c);
             self.len += 1;
             return;
         }
-        let mut result: String = String::new();
-        let mut length = 0;
-        for (index, grapheme) in self.string[..].graphemes(true).enumerate() {
-            length += 1;
-            if index == at {
-                length += 1;
-                result.push(c);
-            }
-            result.push_str(grapheme);
-        }
-        self.len = length;
-        self.string = result;
+        let start = GraphemeCursor::new(byte_index, self.string.len(), true)
+            .prev_boundary(&self.string, 0)
+            .ok()
+            .flatten()
+            .unwrap_or(byte_index);
+        let end = GraphemeCursor::new(byte_index, self.string.len(), true)
+            .next_boundary(&self.string, 0)
+            .ok()
+            .flatten()
+            .unwrap_or(byte_index);
+        let old = self.string[start..end].graphemes(true).count();
+        self.string.insert(byte_index, c);
+        let new = self.string[start..end + c.len_utf8()]
+            .graphemes(true)
+            .count();
+        self.len = self.len + new - old;
     }
     pub fn delete(&mut self, at: usize) {
         if at >= self.len() {
             return;
         }
-        let mut result: String = String::new();
-        let mut length = 0;
-        for (index, grapheme) in self.string[..].graphemes(true).enumerate() {
-            if index != at {
-                length += 1;
-                result.push_str(grapheme);
-            }
+        if self.len == self.string.len() {
+            self.string.remove(at);
+            self.len -= 1;
+        } else if let Some((byte_index, grapheme)) = self.string[..].grapheme_indices(true).nth(at)
+        {
+            let end = byte_index.saturating_add(grapheme.len());
+            self.string.replace_range(byte_index..end, "");
+            self.len -= 1;
         }
-        self.len = length;
-        self.string = result;
     }
+    // The joined row ends the way the appended one did.
     pub fn append(&mut self, new: &Self) {
-        self.string = format!("{}{}", self.string, new.string);
+        self.string.push_str(&new.string);
         self.len += new.len;
+        if new.ending.is_some() {
+            self.ending = new.ending;
+        }
     }
+    // The split-off tail keeps the original line ending, except when the
+    // split is at the very end of the row.
     pub fn split(&mut self, at: usize) -> Self {
-        let mut row: String = String::new();
-        let mut length = 0;
-        let mut splitted_row: String = String::new();
-        let mut splitted_length = 0;
-        for (index, grapheme) in self.string[..].graphemes(true).enumerate() {
-            if index < at {
-                length += 1;
-                row.push_str(grapheme);
-            } else {
-                splitted_length += 1;
-                splitted_row.push_str(grapheme);
-            }
-        }
-
-        self.string = row;
-        self.len = length;
+        let byte_index = self.byte_index(at);
+        let splitted_row = self.string.split_off(byte_index);
+        let splitted_length = self.len.saturating_sub(at);
+        let ending = if at < self.len {
+            self.ending.take()
+        } else {
+            None
+        };
+        self.len = self.len.min(at);
         self.is_highlighted = false;
         Self {
             string: splitted_row,
             len: splitted_length,
             highlighting: Vec::new(),
             is_highlighted: false,
+            unmatched_brackets: Vec::new(),
+            continuation: Continuation::None,
+            ending,
         }
     }
+    // How the line ended on disk, if it came from there.
+    pub fn ending(&self) -> Option<LineEnding> {
+        self.ending
+    }
+    pub fn set_ending(&mut self, ending: Option<LineEnding>) {
+        self.ending = ending;
+    }
+    fn byte_index(&self, at: usize) -> usize {
+        if at >= self.len {
+            return self.string.len();
+        }
+        if self.len == self.string.len() {
+            return at;
+        }
+        self.string[..]
+            .grapheme_indices(true)
+            .nth(at)
+            .map_or(self.string.len(), |(byte_index, _)| byte_index)
+    }
     pub fn as_bytes(&self) -> &[u8] {
         self.string.as_bytes()
     }
-    pub fn find(&self, query: &str, at: usize, direction: SearchDirection) -> Option<usize> {
+    pub fn as_str(&self) -> &str {
+        &self.string
+    }
+    // Replaces matches left to right, resuming after each replaced match so
+    // the replacement itself is never searched again.
+    pub fn replace_all(&mut self, query: &str, replacement: &str, case_sensitive: bool) -> usize {
+        let graphemes: Vec<&str> = self.string[..].graphemes(true).collect();
+        let mut result = String::new();
+        let mut count: usize = 0;
+        let mut at = 0;
+        while let Some(found) = self.find(query, at, SearchDirection::Forward, case_sensitive) {
+            result.extend(graphemes.iter().take(found).skip(at).copied());
+            result.push_str(replacement);
+            let len = self.match_len(found, query, case_sensitive).unwrap_or(0);
+            at = found.saturating_add(len);
+            count = count.saturating_add(1);
+        }
+        if count > 0 {
+            result.extend(graphemes.iter().skip(at).copied());
+            self.len = result[..].graphemes(true).count();
+            self.string = result;
+            self.is_highlighted = false;
+        }
+        count
+    }
+    // Swaps the `len` graphemes starting at `at` for `text`.
+    pub fn replace(&mut self, at: usize, len: usize, text: &str) {
+        let start = self.byte_index(at);
+        let end = self.byte_index(at.saturating_add(len));
+        self.string.replace_range(start..end, text);
+        self.len = self.string[..].graphemes(true).count();
+        self.is_highlighted = false;
+    }
+    // Rewrites the leading whitespace as spaces only, or as tabs topped up
+    // with spaces, keeping the column the text starts at. Returns whether
+    // anything changed.
+    #[allow(clippy::integer_arithmetic)]
+    pub fn retab(&mut self, to_spaces: bool, width: usize) -> bool {
+        let width = width.max(1);
+        let indent_len = self.string.len() - self.string.trim_start_matches([' ', '\t']).len();
+        let columns = self.string[..indent_len].chars().fold(0, |column, c| {
+            if c == '\t' {
+                column + width - column % width
+            } else {
+                column + 1
+            }
+        });
+        let indent = if to_spaces {
+            " ".repeat(columns)
+        } else {
+            "\t".repeat(columns / width) + &" ".repeat(columns % width)
+        };
+        if indent == self.string[..indent_len] {
+            return false;
+        }
+        self.string.replace_range(..indent_len, &indent);
+        self.len = self.string[..].graphemes(true).count();
+        self.is_highlighted = false;
+        true
+    }
+    pub fn trim_end(&mut self) {
+        let trimmed_len = self.string.trim_end().len();
+        if trimmed_len < self.string.len() {
+            self.string.truncate(trimmed_len);
+            self.len = self.string[..].graphemes(true).count();
+            self.is_highlighted = false;
+        }
+    }
+    // Matches against the raw text rather than the rendered one, so a tab
+    // only matches a tab (typed as ^I in the prompt), never spaces, and the
+    // position found counts a tab as one grapheme however wide it is drawn.
+    pub fn find(
+        &self,
+        query: &str,
+        at: usize,
+        direction: SearchDirection,
+        case_sensitive: bool,
+    ) -> Option<usize> {
         if at > self.len || query.is_empty() {
             return None;
         }
@@ -145,44 +340,85 @@ impl Row {
         } else {
             at
         };
-        #[allow(clippy::integer_arithmetic)]
-        let substring: String = self.string[..]
-            .graphemes(true)
-            .skip(start)
-            .take(end - start)
+        // Only matches that start and end on grapheme boundaries count, so a
+        // query never matches half of an accented letter or emoji sequence.
+        let boundaries: Vec<usize> = self.string[..]
+            .grapheme_indices(true)
+            .map(|(byte_index, _)| byte_index)
+            .chain(std::iter::once(self.string.len()))
             .collect();
-        let matching_byte_index = if direction == SearchDirection::Forward {
-            substring.find(query)
-        } else {
-            substring.rfind(query)
+        let is_match = |&index: &usize| {
+            boundaries.get(index).map_or(false, |&byte_index| {
+                match_len(&self.string[byte_index..], query, case_sensitive).map_or(false, |len| {
+                    boundaries
+                        .binary_search(&byte_index.saturating_add(len))
+                        .map_or(false, |match_end| match_end <= end)
+                })
+            })
         };
-        if let Some(matching_byte_index) = matching_byte_index {
-            for (grapheme_index, (byte_index, _)) in
-                substring[..].grapheme_indices(true).enumerate()
-            {
-                if matching_byte_index == byte_index {
-                    #[allow(clippy::integer_arithmetic)]
-                    return Some(start + grapheme_index);
+        if direction == SearchDirection::Forward {
+            (start..end).find(is_match)
+        } else {
+            (start..end).rev().find(is_match)
+        }
+    }
+
+    // How many graphemes the match of `query` at `at` takes up, which without
+    // case sensitivity needn't be as many as the query has.
+    pub fn match_len(&self, at: usize, query: &str, case_sensitive: bool) -> Option<usize> {
+        let start = self.byte_index(at);
+        let len = match_len(&self.string[start..], query, case_sensitive)?;
+        let matched = &self.string[start..start.saturating_add(len)];
+        Some(matched.graphemes(true).count())
+    }
+    // Finds the quotes delimiting the string the cursor is in or next to.
+    // Other quote characters and escaped quotes inside a string are skipped,
+    // and an unterminated string has no pair.
+    pub fn quote_pair(&self, at: usize, opts: &HighlightingOptions) -> Option<(usize, usize)> {
+        if !opts.strings() {
+            return None;
+        }
+        let mut open: Option<(usize, &str)> = None;
+        let mut escaped = false;
+        for (index, grapheme) in self.string[..].graphemes(true).enumerate() {
+            if let Some((start, quote)) = open {
+                if escaped {
+                    escaped = false;
+                } else if grapheme == "\\" && quote.chars().all(|c| opts.escapes_in(c)) {
+                    escaped = true;
+                } else if grapheme == quote {
+                    if start <= at && at <= index.saturating_add(1) {
+                        return Some((start, index));
+                    }
+                    open = None;
                 }
+            } else if index > at {
+                return None;
+            } else if grapheme == "\"" || (grapheme == "'" && opts.single_quoted_strings()) {
+                open = Some((index, grapheme));
             }
         }
         None
     }
 
-    fn highlight_match(&mut self, word: &Option<String>) {
+    fn highlight_match(&mut self, word: &Option<String>, case_sensitive: bool) {
         if let Some(word) = word {
             if word.is_empty() {
                 return;
             }
+            // Step one grapheme past each match rather than past its end, so
+            // overlapping matches are highlighted just like search visits them.
             let mut index = 0;
-            while let Some(search_match) = self.find(word, index, SearchDirection::Forward) {
+            while let Some(search_match) =
+                self.find(word, index, SearchDirection::Forward, case_sensitive)
+            {
                 if let Some(next_index) = search_match.checked_add(word[..].graphemes(true).count())
                 {
                     #[allow(clippy::indexing_slicing)]
-                    for i in index.saturating_add(search_match)..next_index {
+                    for i in search_match..next_index {
                         self.highlighting[i] = highlighting::Type::Match;
                     }
-                    index = next_index;
+                    index = search_match.saturating_add(1);
                 } else {
                     break;
                 }
@@ -190,282 +426,183 @@ impl Row {
         }
     }
 
-    fn highlight_char(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        c: char,
-        chars: &[char],
-    ) -> bool {
-        if opts.characters() && c == '\'' {
-            if let Some(next_char) = chars.get(index.saturating_add(1)) {
-                let closing_index = if *next_char == '\\' {
-                    index.saturating_add(3)
-                } else {
-                    index.saturating_add(2)
-                };
-                if let Some(closing_char) = chars.get(closing_index) {
-                    if *closing_char == '\'' {
-                        for _ in 0..=closing_index.saturating_sub(*index) {
-                            self.highlighting.push(highlighting::Type::Character);
-                            *index += 1;
-                        }
-                        return true;
-                    }
-                }
+    // Words in backticks are left alone, as are words with digits in them.
+    #[allow(clippy::indexing_slicing)]
+    fn highlight_spelling(&mut self, dictionary: Option<&Dictionary>) {
+        let dictionary = match dictionary {
+            Some(dictionary) => dictionary,
+            None => return,
+        };
+        let chars: Vec<char> = self.string.chars().collect();
+        let mut index = 0;
+        while let Some(c) = chars.get(index) {
+            if *c == '`' {
+                index = chars
+                    .iter()
+                    .skip(index.saturating_add(1))
+                    .position(|c| *c == '`')
+                    .map_or(chars.len(), |position| {
+                        index.saturating_add(position).saturating_add(2)
+                    });
+                continue;
             }
-        }
-        false
-    }
-
-    fn highlight_comment(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        c: char,
-        chars: &[char],
-    ) -> bool {
-        if opts.comments() && c == '/' && *index < chars.len() {
-            if let Some(next_char) = chars.get(index.saturating_add(1)) {
-                if *next_char == '/' {
-                    for _ in *index..chars.len() {
-                        self.highlighting.push(highlighting::Type::Comment);
-                        *index += 1;
-                    }
-                    return true;
-                }
-            };
-        }
-        false
-    }
-
-    fn highlight_string(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        c: char,
-        chars: &[char],
-    ) -> bool {
-        if opts.strings() && c == '"' {
-            loop {
-                self.highlighting.push(highlighting::Type::String);
-                *index += 1;
-                if let Some(next_char) = chars.get(*index) {
-                    if *next_char == '"' {
-                        break;
-                    }
-                } else {
-                    break;
-                }
+            let starts_word = c.is_alphabetic()
+                && (index == 0 || !chars[index.saturating_sub(1)].is_alphanumeric());
+            if !starts_word {
+                index = index.saturating_add(1);
+                continue;
             }
-            self.highlighting.push(highlighting::Type::String);
-            *index += 1;
-            return true;
-        }
-        false
-    }
-
-    fn highlight_number(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        c: char,
-        chars: &[char],
-    ) -> bool {
-        if opts.numbers() && c.is_ascii_digit() {
-            if *index > 0 {
-                #[allow(clippy::indexing_slicing, clippy::integer_arithmetic)]
-                let prev_char = chars[*index - 1];
-                if !is_separator(prev_char) {
-                    return false;
+            let mut end = index;
+            while let Some(c) = chars.get(end) {
+                let is_apostrophe = *c == '\''
+                    && chars
+                        .get(end.saturating_add(1))
+                        .map_or(false, |next| next.is_alphabetic());
+                if !c.is_alphanumeric() && !is_apostrophe {
+                    break;
                 }
+                end = end.saturating_add(1);
             }
-            loop {
-                self.highlighting.push(highlighting::Type::Number);
-                *index += 1;
-                if let Some(next_char) = chars.get(*index) {
-                    if *next_char != '.' && !next_char.is_ascii_digit() {
-                        break;
+            let word: String = chars[index..end].iter().collect();
+            if !word.chars().any(|c| c.is_ascii_digit()) && !dictionary.contains(&word) {
+                for hl_type in self.highlighting.iter_mut().take(end).skip(index) {
+                    if *hl_type == highlighting::Type::None {
+                        *hl_type = highlighting::Type::Misspelled;
                     }
-                } else {
-                    break;
                 }
             }
-            return true;
+            index = end;
         }
-        false
     }
 
-    pub fn highlight_str(
-        &mut self,
-        index: &mut usize,
-        substring: &str,
-        chars: &[char],
-        hl_type: highlighting::Type,
-    ) -> bool {
-        if substring.is_empty() {
-            return false;
-        }
-
-        for (substring_index, c) in substring.chars().enumerate() {
-            if let Some(next_char) = chars.get(index.saturating_add(substring_index)) {
-                if *next_char != c {
-                    return false;
+    // Only plain text and comments, so a URL in a string keeps its string
+    // color.
+    fn highlight_urls(&mut self) {
+        let chars: Vec<char> = self.string.chars().collect();
+        for (start, end) in url_spans(&chars) {
+            for hl_type in self.highlighting.iter_mut().take(end).skip(start) {
+                if matches!(
+                    hl_type,
+                    highlighting::Type::None
+                        | highlighting::Type::Misspelled
+                        | highlighting::Type::Comment
+                        | highlighting::Type::MultilineComment
+                ) {
+                    *hl_type = highlighting::Type::Url;
                 }
-            } else {
-                return false;
             }
         }
-
-        for _ in 0..substring.len() {
-            self.highlighting.push(hl_type);
-            *index += 1;
-        }
-        true
     }
-
-    fn highlight_keywords(
+    pub fn url_at(&self, at: usize) -> Option<String> {
+        let chars: Vec<char> = self.string.chars().collect();
+        let at: usize = self.string[..]
+            .graphemes(true)
+            .take(at)
+            .map(|grapheme| grapheme.chars().count())
+            .sum();
+        url_spans(&chars)
+            .into_iter()
+            .find(|(start, end)| (*start..*end).contains(&at))
+            .map(|(start, end)| chars.iter().take(end).skip(start).collect())
+    }
+    // The highlighter only sees the text. Misspellings and search matches
+    // are laid over its result, as they don't depend on the file type.
+    pub fn highlight(
         &mut self,
-        index: &mut usize,
-        chars: &[char],
-        keywords: &[String],
-        hl_type: highlighting::Type,
-    ) -> bool {
-        if *index > 0 {
-            let prev_char = chars[*index - 1];
-            if !is_separator(prev_char) {
-                return false;
-            }
-        }
-        for word in keywords {
-            if *index < chars.len().saturating_sub(word.len()) {
-                #[allow(clippy::indexing_slicing, clippy::integer_arithmetic)]
-                let next_char = chars[*index + word.len()];
-                if !is_separator(next_char) {
-                    continue;
-                }
-            }
-            if self.highlight_str(index, &word, chars, hl_type) {
-                return true;
-            }
+        highlighter: &dyn Highlighter,
+        word: &Option<String>,
+        case_sensitive: bool,
+        dictionary: Option<&Dictionary>,
+        start: Continuation,
+    ) -> Continuation {
+        if self.is_highlighted && word.is_none() {
+            return self.continuation;
         }
-        false
+        let (highlighting, continuation) = highlighter.highlight_row(&self.string, start);
+        self.highlighting = highlighting;
+        self.continuation = continuation;
+        self.highlight_spelling(dictionary);
+        self.highlight_urls();
+        self.highlight_match(word, case_sensitive);
+        self.is_highlighted = true;
+        self.continuation
     }
+}
 
-    fn highlight_primary_keywords(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        chars: &[char],
-    ) -> bool {
-        self.highlight_keywords(
-            index,
-            chars,
-            opts.primary_keywords(),
-            highlighting::Type::PrimaryKeywords,
-        )
+// The length in bytes of `query` at the start of `text`. Without case
+// sensitivity letters are compared by their lowercase forms, which can take up
+// a different number of bytes than the query's.
+fn match_len(text: &str, query: &str, case_sensitive: bool) -> Option<usize> {
+    if case_sensitive {
+        return text.starts_with(query).then_some(query.len());
     }
-    fn highlight_secondary_keywords(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        chars: &[char],
-    ) -> bool {
-        self.highlight_keywords(
-            index,
-            chars,
-            opts.secondary_keywords(),
-            highlighting::Type::SecondaryKeywords,
-        )
-    }
-
-    #[allow(clippy::indexing_slicing, clippy::integer_arithmetic)]
-    fn highlight_multiline_comment(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        c: char,
-        chars: &[char],
-    ) -> bool {
-        if opts.comments() && c == '/' && *index < chars.len() {
-            if let Some(next_char) = chars.get(index.saturating_add(1)) {
-                if *next_char == '*' {
-                    let closing_index =
-                        if let Some(closing_index) = self.string[*index + 2..].find("*/") {
-                            *index + closing_index + 4
-                        } else {
-                            chars.len()
-                        };
-                    for _ in *index..closing_index {
-                        self.highlighting.push(highlighting::Type::MultilineComment);
-                        *index += 1;
-                    }
-                    return true;
-                }
-            };
+    let mut text_chars = text.chars();
+    let mut len: usize = 0;
+    for query_char in query.chars() {
+        let text_char = text_chars.next()?;
+        if text_char != query_char && !text_char.to_lowercase().eq(query_char.to_lowercase()) {
+            return None;
         }
-        false
+        len = len.saturating_add(text_char.len_utf8());
     }
+    Some(len)
+}
 
-    #[allow(clippy::indexing_slicing, clippy::integer_arithmetic)]
-    pub fn highlight(
-        &mut self,
-        opts: &HighlightingOptions,
-        word: &Option<String>,
-        start_with_comment: bool,
-    ) -> bool {
-        let chars: Vec<char> = self.string.chars().collect();
-        if self.is_highlighted && word.is_none() {
-            if let Some(hl_type) = self.highlighting.last() {
-                if *hl_type == highlighting::Type::MultilineComment
-                    && self.string.len() > 1
-                    && self.string[self.string.len() - 2..] == *"*/" {
-                        return true;
-                    }
-            }
-            return false;
+// Finds `http://` and `https://` URLs as char ranges. A URL stops at
+// whitespace or quotes and gives up punctuation that more likely ends the
+// sentence, as well as closing brackets it never opened.
+fn url_spans(chars: &[char]) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let starts_url = ["https://", "http://"].iter().any(|scheme| {
+            scheme
+                .chars()
+                .enumerate()
+                .all(|(offset, c)| chars.get(index.saturating_add(offset)) == Some(&c))
+        });
+        let after_word = index > 0
+            && chars
+                .get(index.saturating_sub(1))
+                .map_or(false, |c| c.is_alphanumeric());
+        if !starts_url || after_word {
+            index = index.saturating_add(1);
+            continue;
         }
-        self.highlighting = Vec::new();
-        let mut index = 0;
-        let mut in_ml_comment = start_with_comment;
-        if in_ml_comment {
-            let closing_index = if let Some(closing_index) = self.string.find("*/") {
-                closing_index + 2
-            } else {
-                chars.len()
+        let mut end = chars
+            .iter()
+            .skip(index)
+            .position(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '`' | '<' | '>'))
+            .map_or(chars.len(), |position| index.saturating_add(position));
+        while end > index {
+            let url = chars.get(index..end).unwrap_or_default();
+            let unbalanced = |open: char, close: char| {
+                url.iter().filter(|c| **c == close).count()
+                    > url.iter().filter(|c| **c == open).count()
             };
-
-            for _ in 0..closing_index {
-                self.highlighting.push(highlighting::Type::MultilineComment);
-            }
-            index = closing_index;
-        }
-        while let Some(c) = chars.get(index) {
-            if self.highlight_multiline_comment(&mut index, &opts, *c, &chars) {
-                in_ml_comment = true;
-                continue;
-            }
-            if self.highlight_char(&mut index, opts, *c, &chars)
-                || self.highlight_comment(&mut index, opts, *c, &chars)
-                || self.highlight_primary_keywords(&mut index, opts, &chars)
-                || self.highlight_secondary_keywords(&mut index, opts, &chars)
-                || self.highlight_string(&mut index, opts, *c, &chars)
-                || self.highlight_number(&mut index, opts, *c, &chars)
-            {
-                continue;
+            let trim = match url.last() {
+                Some('.' | ',' | ';' | ':' | '!' | '?') => true,
+                Some(')') => unbalanced('(', ')'),
+                Some(']') => unbalanced('[', ']'),
+                _ => false,
+            };
+            if !trim {
+                break;
             }
-            self.highlighting.push(highlighting::Type::None);
-            index += 1;
+            end = end.saturating_sub(1);
         }
-        self.highlight_match(word);
-        if in_ml_comment && &self.string[self.string.len().saturating_sub(2)..] != "*/" {
-            return true;
-        } 
-        self.is_highlighted = true;
-        false
+        spans.push((index, end));
+        index = end.max(index.saturating_add(1));
     }
+    spans
 }
 
-fn is_separator(c: char) -> bool {
-    c.is_ascii_punctuation() || c.is_ascii_whitespace()
+fn control_notation(c: char) -> Option<String> {
+    match c {
+        '\t' => None,
+        '\u{7f}' => Some("^?".to_string()),
+        '\u{0}'..='\u{1f}' => Some(format!("^{}", char::from(c as u8 ^ 0x40))),
+        '\u{80}'..='\u{9f}' => Some(format!("<{:02x}>", c as u32)),
+        _ => None,
+    }
 }
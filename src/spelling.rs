@@ -0,0 +1,25 @@
+use std::{collections::HashSet, fs, io};
+
+#[derive(Default, Debug)]
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    pub fn load(path: &str) -> Result<Self, io::Error> {
+        let words = fs::read_to_string(path)?
+            .lines()
+            .map(|word| word.trim().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect();
+        Ok(Self { words })
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        self.words.contains(&word)
+            || word
+                .strip_suffix("'s")
+                .map_or(false, |stem| self.words.contains(stem))
+    }
+}
@@ -0,0 +1,38 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const SWAP_SUFFIX: &str = ".rtext-swap";
+const TEMPORARY_SUFFIX: &str = ".tmp";
+
+pub fn path_for(file_name: &str) -> PathBuf {
+    let path = Path::new(file_name);
+    let name = path
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().to_string());
+    path.with_file_name(format!(".{name}{SWAP_SUFFIX}"))
+}
+
+// Writes to a temporary file first and renames it over the swap file, so a
+// crash mid-write never leaves a truncated swap file behind.
+pub fn write(file_name: &str, content: &str) -> Result<(), io::Error> {
+    let path = path_for(file_name);
+    let mut temporary = path.clone().into_os_string();
+    temporary.push(TEMPORARY_SUFFIX);
+    fs::write(&temporary, content)?;
+    fs::rename(&temporary, &path)
+}
+
+pub fn remove(file_name: &str) {
+    let _ = fs::remove_file(path_for(file_name));
+}
+
+pub fn read_newer(file_name: &str) -> Option<String> {
+    let swap_modified = fs::metadata(path_for(file_name)).ok()?.modified().ok()?;
+    let file_modified = fs::metadata(file_name).and_then(|metadata| metadata.modified());
+    if file_modified.map_or(false, |modified| modified > swap_modified) {
+        return None;
+    }
+    fs::read_to_string(path_for(file_name)).ok()
+}
@@ -0,0 +1,506 @@
+use crate::highlighting::{Continuation, Highlighter, Type};
+use crate::HighlightingOptions;
+
+// Every built-in file type is the same set of rules, switched on and off
+// and filled with keywords by its options.
+impl Highlighter for HighlightingOptions {
+    fn highlight_row(&self, text: &str, start: Continuation) -> (Vec<Type>, Continuation) {
+        let mut scanner = Scanner {
+            text,
+            highlighting: Vec::new(),
+            continuation: Continuation::None,
+        };
+        scanner.scan(self, start);
+        (scanner.highlighting, scanner.continuation)
+    }
+}
+
+struct Scanner<'a> {
+    text: &'a str,
+    highlighting: Vec<Type>,
+    continuation: Continuation,
+}
+
+impl Scanner<'_> {
+    fn highlight_char(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if opts.characters() && c == '\'' {
+            if let Some(next_char) = chars.get(index.saturating_add(1)) {
+                // `'\x7f'` and `'\u{1F600}'` escape more than one character.
+                let closing_index = if *next_char == '\\' {
+                    match chars.get(index.saturating_add(2)) {
+                        Some('x') => index.saturating_add(5),
+                        Some('u') => chars
+                            .iter()
+                            .skip(*index)
+                            .position(|c| *c == '}')
+                            .map_or(*index, |position| {
+                                index.saturating_add(position).saturating_add(1)
+                            }),
+                        _ => index.saturating_add(3),
+                    }
+                } else {
+                    index.saturating_add(2)
+                };
+                if let Some(closing_char) = chars.get(closing_index) {
+                    if *closing_char == '\'' {
+                        for _ in 0..=closing_index.saturating_sub(*index) {
+                            self.highlighting.push(Type::Character);
+                            *index += 1;
+                        }
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    fn highlight_comment(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        chars: &[char],
+    ) -> bool {
+        if !opts.comments() {
+            return false;
+        }
+        for comment_start in opts.comment_starts() {
+            if starts_with_at(chars, *index, comment_start) {
+                for _ in *index..chars.len() {
+                    self.highlighting.push(Type::Comment);
+                    *index += 1;
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    fn highlight_section(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if opts.sections() && c == '[' && is_line_start(chars, *index) {
+            let mut closing_index = chars
+                .iter()
+                .skip(*index)
+                .position(|c| *c == ']')
+                .map_or(chars.len(), |position| index.saturating_add(position));
+            while chars.get(closing_index) == Some(&']') {
+                closing_index = closing_index.saturating_add(1);
+            }
+            for _ in *index..closing_index {
+                self.highlighting.push(Type::Section);
+                *index += 1;
+            }
+            return true;
+        }
+        false
+    }
+
+    fn highlight_key(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        chars: &[char],
+    ) -> bool {
+        if !opts.keys() || !is_line_start(chars, *index) {
+            return false;
+        }
+        if let Some(equals_index) = chars.iter().skip(*index).position(|c| *c == '=') {
+            let key_end = chars[*index..index.saturating_add(equals_index)]
+                .iter()
+                .rposition(|c| !c.is_whitespace())
+                .map_or(*index, |position| {
+                    index.saturating_add(position).saturating_add(1)
+                });
+            if key_end == *index {
+                return false;
+            }
+            for _ in *index..key_end {
+                self.highlighting.push(Type::Key);
+                *index += 1;
+            }
+            return true;
+        }
+        false
+    }
+
+    fn highlight_string(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if opts.strings() && (c == '"' || (c == '\'' && opts.single_quoted_strings())) {
+            self.highlighting.push(Type::String);
+            *index += 1;
+            while let Some(next_char) = chars.get(*index) {
+                // A backslash escapes the next character, so `\"` doesn't end
+                // the string, unless the string is a literal one.
+                if *next_char == '\\' && opts.escapes_in(c) {
+                    for _ in 0..2 {
+                        if chars.get(*index).is_some() {
+                            self.highlighting.push(Type::String);
+                            *index += 1;
+                        }
+                    }
+                    continue;
+                }
+                if *next_char == c {
+                    self.highlighting.push(Type::String);
+                    *index += 1;
+                    break;
+                }
+                if c == '"' && self.highlight_variable(index, opts, *next_char, chars) {
+                    continue;
+                }
+                self.highlighting.push(Type::String);
+                *index += 1;
+            }
+            return true;
+        }
+        false
+    }
+
+    // Raw strings like `r"..."`, `r#"..."#` or `br"..."` ignore escapes and
+    // end only at a quote followed by as many hashes as they started with.
+    #[allow(clippy::indexing_slicing)]
+    fn highlight_raw_string(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if !opts.raw_strings() || (*index > 0 && !is_separator(chars[*index - 1], opts)) {
+            return false;
+        }
+        let prefix = if c == 'b' {
+            index.saturating_add(1)
+        } else {
+            *index
+        };
+        if chars.get(prefix) != Some(&'r') {
+            return false;
+        }
+        let hashes = chars
+            .iter()
+            .skip(prefix.saturating_add(1))
+            .take_while(|c| **c == '#')
+            .count();
+        let quote = prefix.saturating_add(1).saturating_add(hashes);
+        if chars.get(quote) != Some(&'"') {
+            return false;
+        }
+        for _ in *index..=quote {
+            self.highlighting.push(Type::String);
+            *index += 1;
+        }
+        if !self.highlight_raw_string_body(index, hashes, chars) {
+            self.continuation = Continuation::RawString(hashes);
+        }
+        true
+    }
+
+    // Returns false when the row ends before the raw string does.
+    fn highlight_raw_string_body(
+        &mut self,
+        index: &mut usize,
+        hashes: usize,
+        chars: &[char],
+    ) -> bool {
+        while let Some(c) = chars.get(*index) {
+            let closes = *c == '"'
+                && (1..=hashes).all(|offset| chars.get(index.saturating_add(offset)) == Some(&'#'));
+            let len = if closes { hashes.saturating_add(1) } else { 1 };
+            for _ in 0..len {
+                self.highlighting.push(Type::String);
+                *index += 1;
+            }
+            if closes {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn highlight_variable(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if !opts.variables() || c != '$' {
+            return false;
+        }
+        let start = index.saturating_add(1);
+        let end = match chars.get(start) {
+            Some('{') => chars
+                .iter()
+                .skip(start)
+                .position(|c| *c == '}')
+                .map_or(chars.len(), |position| {
+                    start.saturating_add(position).saturating_add(1)
+                }),
+            Some(next_char) if next_char.is_alphabetic() || *next_char == '_' => chars
+                .iter()
+                .skip(start)
+                .position(|c| !c.is_alphanumeric() && *c != '_')
+                .map_or(chars.len(), |position| start.saturating_add(position)),
+            Some(next_char) if next_char.is_ascii_digit() || "?#@*$!-".contains(*next_char) => {
+                start.saturating_add(1)
+            }
+            _ => return false,
+        };
+        for _ in *index..end {
+            self.highlighting.push(Type::Variable);
+            *index += 1;
+        }
+        true
+    }
+
+    fn highlight_number(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if opts.numbers() && c.is_ascii_digit() {
+            if *index > 0 {
+                #[allow(clippy::indexing_slicing, clippy::integer_arithmetic)]
+                let prev_char = chars[*index - 1];
+                if !is_separator(prev_char, opts) {
+                    return false;
+                }
+            }
+            for _ in *index..number_end(chars, *index) {
+                self.highlighting.push(Type::Number);
+                *index += 1;
+            }
+            return true;
+        }
+        false
+    }
+
+    pub fn highlight_str(
+        &mut self,
+        index: &mut usize,
+        substring: &str,
+        chars: &[char],
+        hl_type: Type,
+    ) -> bool {
+        if substring.is_empty() {
+            return false;
+        }
+
+        for (substring_index, c) in substring.chars().enumerate() {
+            if let Some(next_char) = chars.get(index.saturating_add(substring_index)) {
+                if *next_char != c {
+                    return false;
+                }
+            } else {
+                return false;
+            }
+        }
+
+        for _ in 0..substring.len() {
+            self.highlighting.push(hl_type);
+            *index += 1;
+        }
+        true
+    }
+
+    fn highlight_keywords(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        chars: &[char],
+        keywords: &[String],
+        hl_type: Type,
+    ) -> bool {
+        if *index > 0 {
+            let prev_char = chars[*index - 1];
+            if !is_separator(prev_char, opts) {
+                return false;
+            }
+        }
+        for word in keywords {
+            if *index < chars.len().saturating_sub(word.len()) {
+                #[allow(clippy::indexing_slicing, clippy::integer_arithmetic)]
+                let next_char = chars[*index + word.len()];
+                if !is_separator(next_char, opts) {
+                    continue;
+                }
+            }
+            if self.highlight_str(index, &word, chars, hl_type) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn highlight_primary_keywords(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        chars: &[char],
+    ) -> bool {
+        self.highlight_keywords(
+            index,
+            opts,
+            chars,
+            opts.primary_keywords(),
+            Type::PrimaryKeywords,
+        )
+    }
+    fn highlight_secondary_keywords(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        chars: &[char],
+    ) -> bool {
+        self.highlight_keywords(
+            index,
+            opts,
+            chars,
+            opts.secondary_keywords(),
+            Type::SecondaryKeywords,
+        )
+    }
+
+    // A comment left open at the end of the row carries over to the next.
+    fn highlight_multiline_comment(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if opts.multiline_comments() && c == '/' && chars.get(index.saturating_add(1)) == Some(&'*')
+        {
+            let start = *index;
+            self.highlight_comment_body(index, start.saturating_add(2), chars);
+            return true;
+        }
+        false
+    }
+
+    // Colors from `index` through the `*/` found at or after `from`, or to the
+    // end of the row when there's none.
+    fn highlight_comment_body(&mut self, index: &mut usize, from: usize, chars: &[char]) {
+        let closing_index =
+            (from..chars.len()).find(|closing_index| starts_with_at(chars, *closing_index, "*/"));
+        let end =
+            closing_index.map_or(chars.len(), |closing_index| closing_index.saturating_add(2));
+        if closing_index.is_none() {
+            self.continuation = Continuation::Comment;
+        }
+        while *index < end {
+            self.highlighting.push(Type::MultilineComment);
+            *index = index.saturating_add(1);
+        }
+    }
+
+    #[allow(clippy::indexing_slicing, clippy::integer_arithmetic)]
+    fn scan(&mut self, opts: &HighlightingOptions, start: Continuation) {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut index = 0;
+        if start == Continuation::Comment {
+            self.highlight_comment_body(&mut index, 0, &chars);
+        }
+        if let Continuation::RawString(hashes) = start {
+            if !self.highlight_raw_string_body(&mut index, hashes, &chars) {
+                self.continuation = start;
+            }
+        }
+        while let Some(c) = chars.get(index) {
+            if self.highlight_multiline_comment(&mut index, opts, *c, &chars)
+                || self.highlight_char(&mut index, opts, *c, &chars)
+                || self.highlight_comment(&mut index, opts, &chars)
+                || self.highlight_section(&mut index, opts, *c, &chars)
+                || self.highlight_key(&mut index, opts, &chars)
+                || self.highlight_primary_keywords(&mut index, opts, &chars)
+                || self.highlight_secondary_keywords(&mut index, opts, &chars)
+                || self.highlight_raw_string(&mut index, opts, *c, &chars)
+                || self.highlight_string(&mut index, opts, *c, &chars)
+                || self.highlight_variable(&mut index, opts, *c, &chars)
+                || self.highlight_number(&mut index, opts, *c, &chars)
+            {
+                continue;
+            }
+            self.highlighting.push(Type::None);
+            index += 1;
+        }
+    }
+}
+
+fn is_separator(c: char, opts: &HighlightingOptions) -> bool {
+    !opts.is_word_char(c)
+}
+
+// Finds where the number starting at `start` ends, taking in `0x`, `0o` and
+// `0b` prefixes with their digits, `_` separators, a fraction and an
+// exponent such as `e-3`.
+fn number_end(chars: &[char], start: usize) -> usize {
+    let digits_end = |from: usize, radix: u32| {
+        chars
+            .iter()
+            .skip(from)
+            .position(|c| !c.is_digit(radix) && *c != '_')
+            .map_or(chars.len(), |position| from.saturating_add(position))
+    };
+    let radix = match (chars.get(start), chars.get(start.saturating_add(1))) {
+        (Some('0'), Some('x' | 'X')) => Some(16),
+        (Some('0'), Some('o' | 'O')) => Some(8),
+        (Some('0'), Some('b' | 'B')) => Some(2),
+        _ => None,
+    };
+    if let Some(radix) = radix {
+        let digits = start.saturating_add(2);
+        let end = digits_end(digits, radix);
+        // A bare prefix like `0x` is only the zero.
+        return if end > digits {
+            end
+        } else {
+            start.saturating_add(1)
+        };
+    }
+    let is_digit_at = |index: usize| chars.get(index).map_or(false, char::is_ascii_digit);
+    let mut end = digits_end(start, 10);
+    if chars.get(end) == Some(&'.') && is_digit_at(end.saturating_add(1)) {
+        end = digits_end(end.saturating_add(1), 10);
+    }
+    if matches!(chars.get(end), Some('e' | 'E')) {
+        let sign = usize::from(matches!(chars.get(end.saturating_add(1)), Some('+' | '-')));
+        let exponent = end.saturating_add(1).saturating_add(sign);
+        if is_digit_at(exponent) {
+            end = digits_end(exponent, 10);
+        }
+    }
+    end
+}
+
+fn is_line_start(chars: &[char], index: usize) -> bool {
+    chars.iter().take(index).all(|c| c.is_whitespace())
+}
+
+fn starts_with_at(chars: &[char], index: usize, pattern: &str) -> bool {
+    !pattern.is_empty()
+        && pattern
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| chars.get(index.saturating_add(offset)) == Some(&c))
+}
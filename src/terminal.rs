@@ -1,16 +1,28 @@
 use crate::Position;
 use std::{
+    cell::RefCell,
     fmt::Debug,
     io::{self, Write},
+    rc::Rc,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
 };
 use termion::{
     color,
-    event::Key,
+    event::{Event, Key},
     input::TermRead,
     raw::{IntoRawMode, RawTerminal},
 };
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CursorShape {
+    Default,
+    Block,
+    Bar,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Size {
     pub width: u16,
     pub height: u16,
@@ -18,7 +30,33 @@ pub struct Size {
 
 pub struct Terminal {
     size: Size,
-    _raw_term: RawTerminal<std::io::Stdout>,
+    bar_rows: u16,
+    wanted_bar_rows: u16,
+    is_tty: bool,
+    input: Receiver<Result<(Key, bool), io::Error>>,
+    shift: bool,
+    output: RefCell<Box<dyn Write>>,
+    frame: RefCell<String>,
+    _raw_term: Option<RawTerminal<std::io::Stdout>>,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Capture {
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).to_string()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 impl Debug for Terminal {
@@ -37,63 +75,203 @@ impl Terminal {
                 width,
                 height: height.saturating_sub(2),
             },
-            _raw_term: std::io::stdout().into_raw_mode()?,
+            bar_rows: height.min(2),
+            wanted_bar_rows: 2,
+            is_tty: termion::is_tty(&io::stdout()),
+            input: spawn_reader(io::stdin().events().filter_map(|event| match event {
+                Ok(Event::Key(key)) => Some(Ok((key, false))),
+                Ok(Event::Unsupported(bytes)) => shifted_key(&bytes).map(|key| Ok((key, true))),
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            })),
+            shift: false,
+            output: RefCell::new(Box::new(io::stdout())),
+            frame: RefCell::new(String::new()),
+            _raw_term: Some(std::io::stdout().into_raw_mode()?),
         })
     }
 
+    pub fn scripted(size: Size, keys: Vec<Key>, output: impl Write + 'static) -> Self {
+        Self {
+            size: Size {
+                width: size.width,
+                height: size.height.saturating_sub(2),
+            },
+            bar_rows: size.height.min(2),
+            wanted_bar_rows: 2,
+            is_tty: false,
+            input: spawn_reader(keys.into_iter().map(|key| Ok((key, false)))),
+            shift: false,
+            output: RefCell::new(Box::new(output)),
+            frame: RefCell::new(String::new()),
+            _raw_term: None,
+        }
+    }
+
     pub fn size(&self) -> Size {
         self.size
     }
 
-    pub fn clear_screen() {
-        print!("{}", termion::clear::All);
+    // Gives `rows` of the terminal to the bars and the rest to the text, as
+    // far as there are rows to give.
+    pub fn set_bar_rows(&mut self, rows: u16) {
+        let total = self.size.height.saturating_add(self.bar_rows);
+        self.wanted_bar_rows = rows;
+        self.bar_rows = rows.min(total);
+        self.size.height = total.saturating_sub(self.bar_rows);
+    }
+
+    // Takes on a new size for the whole terminal, giving the bars the rows
+    // they were given before as far as they still fit.
+    pub fn resize(&mut self, size: Size) {
+        self.bar_rows = self.wanted_bar_rows.min(size.height);
+        self.size = Size {
+            width: size.width,
+            height: size.height.saturating_sub(self.bar_rows),
+        };
+    }
+
+    // Asks the terminal for its size again, so a resize shows up on the next
+    // frame. Scripted terminals keep the size they were given.
+    pub fn update_size(&mut self) {
+        if !self.is_tty {
+            return;
+        }
+        if let Ok((width, height)) = termion::terminal_size() {
+            self.resize(Size { width, height });
+        }
+    }
+
+    // Rows left for the status and message bars. On terminals shorter than
+    // three rows the message bar is dropped first, then the status bar.
+    pub fn bar_rows(&self) -> u16 {
+        self.bar_rows
+    }
+
+    pub fn print(&self, text: &str) {
+        self.frame.borrow_mut().push_str(text);
+    }
+
+    pub fn print_line(&self, line: &str) {
+        self.print(&format!("{line}\r\n"));
+    }
+
+    pub fn clear_screen(&self) {
+        self.print(&format!("{}", termion::clear::All));
+    }
+
+    pub fn clear_current_line(&self) {
+        self.print(&format!("{}", termion::clear::CurrentLine));
     }
 
-    pub fn clear_current_line() {
-        print!("{}", termion::clear::CurrentLine);
+    pub fn flush(&self) -> Result<(), io::Error> {
+        let frame = self.frame.replace(String::new());
+        let mut output = self.output.borrow_mut();
+        output.write_all(frame.as_bytes())?;
+        output.flush()
     }
 
-    pub fn flush() -> Result<(), io::Error> {
-        io::stdout().flush()
+    // DECSCUSR, steady variants. Anything but a terminal would only get
+    // stray bytes, so output that isn't one gets none.
+    pub fn set_cursor_shape(&self, shape: CursorShape) {
+        if !self.is_tty {
+            return;
+        }
+        let code = match shape {
+            CursorShape::Default => 0,
+            CursorShape::Block => 2,
+            CursorShape::Bar => 6,
+        };
+        self.print(&format!("\x1b[{code} q"));
     }
 
     #[allow(clippy::cast_possible_truncation)]
-    pub fn cursor_position(position: &Position) {
+    pub fn cursor_position(&self, position: &Position) {
         let Position { x, y } = position;
         let x = x.saturating_add(1) as u16;
         let y = y.saturating_add(1) as u16;
-        print!("{}", termion::cursor::Goto(x, y));
+        self.print(&format!("{}", termion::cursor::Goto(x, y)));
+    }
+
+    pub fn read_key(&mut self) -> Result<Key, std::io::Error> {
+        let (key, shift) = self.input.recv().unwrap_or_else(|_| Err(no_more_keys()))?;
+        self.shift = shift;
+        Ok(key)
     }
 
-    pub fn read_key() -> Result<Key, std::io::Error> {
-        loop {
-            if let Some(key) = io::stdin().lock().keys().next() {
-                return key;
+    // Waits at most `timeout` for a key, returning `None` if none came.
+    pub fn read_key_timeout(&mut self, timeout: Duration) -> Result<Option<Key>, io::Error> {
+        match self.input.recv_timeout(timeout) {
+            Ok(key) => {
+                let (key, shift) = key?;
+                self.shift = shift;
+                Ok(Some(key))
             }
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(no_more_keys()),
         }
     }
 
-    pub fn cursor_hide() {
-        print!("{}", termion::cursor::Hide);
+    // Whether Shift was held with the last key read.
+    pub fn shift(&self) -> bool {
+        self.shift
     }
 
-    pub fn cursor_show() {
-        print!("{}", termion::cursor::Show);
+    pub fn cursor_hide(&self) {
+        self.print(&format!("{}", termion::cursor::Hide));
     }
 
-    pub fn set_bg_color(color: color::Rgb) {
-        print!("{}", color::Bg(color));
+    pub fn cursor_show(&self) {
+        self.print(&format!("{}", termion::cursor::Show));
     }
 
-    pub fn reset_bg_color() {
-        print!("{}", color::Bg(color::Reset))
+    pub fn set_bg_color(&self, color: color::Rgb) {
+        self.print(&format!("{}", color::Bg(color)));
     }
 
-    pub fn set_fg_color(color: color::Rgb) {
-        print!("{}", color::Fg(color));
+    pub fn reset_bg_color(&self) {
+        self.print(&format!("{}", color::Bg(color::Reset)));
     }
 
-    pub fn reset_fg_color() {
-        print!("{}", color::Fg(color::Reset));
+    pub fn set_fg_color(&self, color: color::Rgb) {
+        self.print(&format!("{}", color::Fg(color)));
     }
+
+    pub fn reset_fg_color(&self) {
+        self.print(&format!("{}", color::Fg(color::Reset)));
+    }
+}
+
+// Keys are read on their own thread so the editor can wait for input with a
+// timeout. The channel disconnects once the input runs out.
+fn spawn_reader(
+    keys: impl Iterator<Item = Result<(Key, bool), io::Error>> + Send + 'static,
+) -> Receiver<Result<(Key, bool), io::Error>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for key in keys {
+            if sender.send(key).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+// termion doesn't know Shift with the cursor keys, in either the xterm or
+// the rxvt form, so they come through as the plain key with Shift held.
+fn shifted_key(bytes: &[u8]) -> Option<Key> {
+    match bytes {
+        b"\x1b[1;2A" | b"\x1b[a" => Some(Key::Up),
+        b"\x1b[1;2B" | b"\x1b[b" => Some(Key::Down),
+        b"\x1b[1;2C" | b"\x1b[c" => Some(Key::Right),
+        b"\x1b[1;2D" | b"\x1b[d" => Some(Key::Left),
+        b"\x1b[1;2H" => Some(Key::Home),
+        b"\x1b[1;2F" => Some(Key::End),
+        _ => None,
+    }
+}
+
+fn no_more_keys() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "no more keys to read")
 }
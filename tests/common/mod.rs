@@ -0,0 +1,85 @@
+#![allow(dead_code)]
+
+use rtext::{Capture, Document, Editor, EditorBuilder, Size, Terminal};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use termion::event::Key;
+
+pub const SIZE: Size = Size {
+    width: 80,
+    height: 10,
+};
+
+// A directory of its own for each test, removed again when the test is done.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "rtext-test-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self, name: &str) -> String {
+        self.0.join(name).to_string_lossy().to_string()
+    }
+
+    // Writes `content` to `name` in the directory and returns its path.
+    pub fn file(&self, name: &str, content: &str) -> String {
+        let path = self.path(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Runs the editor until it has read every key, returning it along with
+// everything it drew.
+pub fn run(builder: EditorBuilder, size: Size, keys: Vec<Key>) -> (Editor, String) {
+    let capture = Capture::default();
+    let terminal = Terminal::scripted(size, keys, capture.clone());
+    let mut editor = builder.terminal(terminal).build();
+    editor.run();
+    (editor, capture.contents())
+}
+
+pub fn run_document(document: Document, keys: Vec<Key>) -> (Editor, String) {
+    run(EditorBuilder::new().document(document), SIZE, keys)
+}
+
+// Types `text` into an empty document, then presses `keys`.
+pub fn run_typed(text: &str, keys: Vec<Key>) -> Editor {
+    let mut all = typed(text);
+    all.extend(keys);
+    run_document(Document::default(), all).0
+}
+
+pub fn typed(text: &str) -> Vec<Key> {
+    text.chars().map(Key::Char).collect()
+}
+
+pub fn rows(editor: &Editor) -> Vec<String> {
+    document_rows(editor.document())
+}
+
+pub fn document_rows(document: &Document) -> Vec<String> {
+    (0..document.len())
+        .filter_map(|y| document.row(y).map(|row| row.as_str().to_string()))
+        .collect()
+}
+
+pub fn cursor(editor: &Editor) -> (usize, usize) {
+    let position = editor.cursor();
+    (position.x, position.y)
+}
@@ -0,0 +1,526 @@
+mod common;
+
+use common::{document_rows, TempDir};
+use rtext::{Document, FileType, HighlightType, Position, SearchDirection};
+use termion::color::Fg;
+
+#[test]
+fn one_row_per_line() {
+    let dir = TempDir::new();
+    for (content, len) in [("a\nb\nc", 3), ("a\nb\nc\n", 3), ("", 0), ("\n", 1)] {
+        let path = dir.file("a.txt", content);
+        let document = Document::open(&path).unwrap();
+        assert_eq!(document.len(), len, "{content:?}");
+    }
+    let path = dir.file("a.txt", "one\r\ntwo");
+    assert_eq!(
+        document_rows(&Document::open(&path).unwrap()),
+        vec!["one", "two"]
+    );
+}
+
+#[test]
+fn save_round_trips_the_exact_bytes() {
+    let dir = TempDir::new();
+    for content in ["a\nb", "a\nb\n", "", "\n", "\n\n"] {
+        let path = dir.file("a.txt", content);
+        let mut document = Document::open(&path).unwrap();
+        document.save().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            content,
+            "{content:?}"
+        );
+    }
+}
+
+#[test]
+fn indentation_is_detected_on_open() {
+    let dir = TempDir::new();
+    for (content, unit) in [
+        ("fn a() {\n\tb();\n\tif c {\n\t\td();\n\t}\n}\n", "\t"),
+        ("a:\n  b:\n    c: 1\n  d: 2\n", "  "),
+        ("def a():\n    if b:\n        c()\n    return d\n", "    "),
+        ("no\nindentation\n", "\t"),
+        ("", "\t"),
+    ] {
+        let path = dir.file("a.txt", content);
+        let document = Document::open(&path).unwrap();
+        assert_eq!(document.indent_unit(), unit, "{content:?}");
+    }
+}
+
+fn rust_document(text: &str) -> Document {
+    let mut document = Document::default();
+    document.set_file_type(FileType::from("a.rs"));
+    document
+        .insert_text(&Position { x: 0, y: 0 }, text)
+        .unwrap();
+    document
+}
+
+fn unmatched_brackets(text: &str) -> Vec<(usize, usize)> {
+    let mut document = rust_document(text);
+    document.refresh_brackets();
+    (0..document.len())
+        .flat_map(|y| {
+            let row = document.row(y).unwrap();
+            row.unmatched_brackets.iter().map(move |x| (*x, y))
+        })
+        .collect()
+}
+
+#[test]
+fn unbalanced_brackets() {
+    assert_eq!(unmatched_brackets("fn a() {\n    b(1);\n"), vec![(7, 0)]);
+    assert_eq!(unmatched_brackets("fn a() {\n}\n}"), vec![(0, 2)]);
+    assert_eq!(unmatched_brackets("a(b[c)]"), vec![(5, 0), (1, 0)]);
+    assert_eq!(unmatched_brackets("fn a() {\n    \"}\"; // )\n}"), vec![]);
+}
+
+#[cfg(unix)]
+#[test]
+fn save_keeps_the_file_mode() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    let dir = TempDir::new();
+    let path = dir.file("run.sh", "echo hi\n");
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o750)).unwrap();
+    let old = std::fs::metadata(&path).unwrap();
+    let mut document = Document::open(&path).unwrap();
+    document.insert(&Position { x: 0, y: 0 }, '#').unwrap();
+    document.save().unwrap();
+    // The file was replaced by a new one, which took over the mode.
+    let metadata = std::fs::metadata(&path).unwrap();
+    assert_ne!(metadata.ino(), old.ino());
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o750);
+    assert_eq!((metadata.uid(), metadata.gid()), (old.uid(), old.gid()));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "#echo hi\n");
+    // Nothing is left behind next to it.
+    assert_eq!(std::fs::read_dir(dir.path("")).unwrap().count(), 1);
+}
+
+#[test]
+fn inserting_past_the_end_is_an_error() {
+    let mut document = Document::default();
+    assert!(document.insert(&Position { x: 0, y: 1 }, 'a').is_err());
+    assert!(document.insert(&Position { x: 1, y: 0 }, 'a').is_err());
+    document.insert(&Position { x: 0, y: 0 }, 'a').unwrap();
+    document.insert(&Position { x: 1, y: 0 }, '\n').unwrap();
+    // The line after the last row takes text too, making a new row.
+    document.insert(&Position { x: 0, y: 2 }, '\n').unwrap();
+    assert_eq!(document.len(), 3);
+    assert!(document.insert(&Position { x: 0, y: 4 }, 'b').is_err());
+    document.insert(&Position { x: 0, y: 3 }, 'b').unwrap();
+    assert_eq!(document.content(), "a\n\n\nb\n");
+    assert!(document.insert_text(&Position { x: 0, y: 9 }, "x").is_err());
+}
+
+// Marks the chars a rendered row draws in the search match color.
+fn matched(document: &Document, y: usize) -> String {
+    let match_color = format!("{}", Fg(HighlightType::Match.to_color()));
+    let rendered = document.row(y).unwrap().render(0, 80, None, None, None);
+    let mut marks = String::new();
+    let mut in_match = false;
+    let mut rest = rendered.as_str();
+    while let Some(c) = rest.chars().next() {
+        if c == '\u{1b}' {
+            let end = rest.find('m').unwrap() + 1;
+            in_match = rest[..end] == match_color;
+            rest = &rest[end..];
+        } else {
+            marks.push(if in_match { 'm' } else { '.' });
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    marks
+}
+
+#[test]
+fn highlighted_matches_are_the_ones_search_finds() {
+    let mut document = Document::default();
+    let text = "a.b A.B a*b\naab.b";
+    document
+        .insert_text(&Position { x: 0, y: 0 }, text)
+        .unwrap();
+    for (query, case_sensitive, first, second) in [
+        ("a.b", true, "mmm........", "....."),
+        ("a.b", false, "mmm.mmm....", "....."),
+        ("A.B", true, "....mmm....", "....."),
+        ("a*b", true, "........mmm", "....."),
+        ("b.b", true, "...........", "..mmm"),
+    ] {
+        let word = Some(query.to_string());
+        document.highlight(&word, case_sensitive, None, None);
+        assert_eq!(matched(&document, 0), first, "{query} {case_sensitive}");
+        assert_eq!(matched(&document, 1), second, "{query} {case_sensitive}");
+        let found: Vec<(usize, usize)> = document
+            .find_all(query, 10, case_sensitive)
+            .iter()
+            .map(|position| (position.x, position.y))
+            .collect();
+        let marked: Vec<(usize, usize)> = [first, second]
+            .iter()
+            .enumerate()
+            .flat_map(|(y, marks)| marks.match_indices("mmm").map(move |(x, _)| (x, y)))
+            .collect();
+        assert_eq!(found, marked, "{query} {case_sensitive}");
+        document.unhighlight_all();
+    }
+}
+
+#[test]
+fn inserted_text_keeps_its_own_indentation() {
+    let dir = TempDir::new();
+    let path = dir.file("a.rs", "fn a() {\n    x();\n}\n");
+    let mut document = Document::open(&path).unwrap();
+    let end = document
+        .insert_text(
+            &Position { x: 4, y: 1 },
+            "if y {\n        z();\n    }\n    ",
+        )
+        .unwrap();
+    assert_eq!(
+        document.content(),
+        "fn a() {\n    if y {\n        z();\n    }\n    x();\n}\n"
+    );
+    assert_eq!((end.x, end.y), (4, 4));
+    document.undo();
+    assert_eq!(document.content(), "fn a() {\n    x();\n}\n");
+
+    let mut document = Document::default();
+    let end = document
+        .insert_text(&Position { x: 0, y: 0 }, "a\r\nb")
+        .unwrap();
+    assert_eq!(document_rows(&document), vec!["a", "b"]);
+    assert_eq!((end.x, end.y), (1, 1));
+}
+
+#[test]
+fn replace_all_is_one_undo_step() {
+    let mut document = Document::default();
+    document
+        .insert_text(&Position { x: 0, y: 0 }, "aaaa ab\nxyz\naé aé")
+        .unwrap();
+    document.end_undo_group();
+    // Matches don't overlap and replacements aren't searched again.
+    assert_eq!(document.replace_all("aa", "a", true, None), 2);
+    assert_eq!(document.content(), "aa ab\nxyz\naé aé\n");
+    assert_eq!(document.replace_all("q", "z", true, None), 0);
+    assert_eq!(document.replace_all("a", "aa", true, None), 5);
+    assert_eq!(document.content(), "aaaa aab\nxyz\naaé aaé\n");
+    assert_eq!(document.replace_all("y", "\n", true, None), 1);
+    assert_eq!(
+        document_rows(&document),
+        vec!["aaaa aab", "x", "z", "aaé aaé"]
+    );
+    assert_eq!(document.row(3).unwrap().len(), 7);
+
+    document.undo();
+    assert_eq!(document.content(), "aaaa aab\nxyz\naaé aaé\n");
+    document.undo();
+    document.undo();
+    assert_eq!(document.content(), "aaaa ab\nxyz\naé aé\n");
+}
+
+#[test]
+fn mixed_line_endings_and_indentation() {
+    let dir = TempDir::new();
+    for (content, endings, indent) in [
+        ("a\r\nb\nc", true, false),
+        ("a\r\nb\r\n", false, false),
+        ("a\n\tb\n    c\n", false, true),
+        ("a\n \tb\n", false, true),
+        ("/*\n * b\n */\n\tc\n", false, false),
+        ("\ta\n\t\tb\n", false, false),
+        ("  a\n    b\n", false, false),
+    ] {
+        let path = dir.file("a.txt", content);
+        let document = Document::open(&path).unwrap();
+        assert_eq!(document.mixed_line_endings(), endings, "{content:?}");
+        assert_eq!(document.mixed_indent(), indent, "{content:?}");
+    }
+}
+
+#[test]
+fn kept_line_endings_survive_whole_buffer_edits() {
+    let dir = TempDir::new();
+    let content = "a;b\r\nc\nd;e\r\nf";
+    let path = dir.file("a.txt", content);
+    let mut document = Document::open(&path).unwrap();
+    document.set_preserve_line_endings(true);
+    document.save().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+
+    document.replace_content(&document.content());
+    assert_eq!(document.replace_all(";", "\n", true, None), 2);
+    assert_eq!(document_rows(&document), vec!["a", "b", "c", "d", "e", "f"]);
+    // New rows end like rows opened with Enter, in the file's own ending.
+    document.save().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "a\r\nb\r\nc\nd\r\ne\r\nf"
+    );
+}
+
+#[test]
+fn retab_converts_only_the_indentation() {
+    let mut document = Document::default();
+    document
+        .insert_text(&Position { x: 0, y: 0 }, "a\n\tb\tc\n  \td\n      e\n")
+        .unwrap();
+    document.end_undo_group();
+    assert_eq!(document.retab(true, 4), 2);
+    assert_eq!(document.content(), "a\n    b\tc\n    d\n      e\n\n");
+    assert_eq!(document.retab(true, 4), 0);
+    assert_eq!(document.retab(false, 4), 3);
+    assert_eq!(document.content(), "a\n\tb\tc\n\td\n\t  e\n\n");
+
+    document.undo();
+    assert_eq!(document.content(), "a\n    b\tc\n    d\n      e\n\n");
+    document.undo();
+    assert_eq!(document.content(), "a\n\tb\tc\n  \td\n      e\n\n");
+}
+
+#[test]
+fn deleting_nothing_is_not_an_edit() {
+    let mut document = Document::default();
+    document
+        .insert_text(&Position { x: 0, y: 0 }, "ab\ncd")
+        .unwrap();
+    let revision = document.revision();
+    document.delete(&Position { x: 2, y: 1 });
+    document.delete(&Position { x: 0, y: 2 });
+    assert_eq!(document.revision(), revision);
+    document.undo();
+    assert!(document.is_empty());
+
+    document.redo();
+    document.delete(&Position { x: 2, y: 0 });
+    assert_ne!(document.revision(), revision);
+    assert_eq!(document_rows(&document), vec!["abcd"]);
+}
+
+#[test]
+fn redo_reapplies_what_undo_took_back() {
+    let mut document = Document::default();
+    document
+        .insert_text(&Position { x: 0, y: 0 }, "one")
+        .unwrap();
+    document
+        .insert_text(&Position { x: 3, y: 0 }, "\ntwo")
+        .unwrap();
+    document.undo();
+    document.undo();
+    assert!(document.is_empty());
+    let cursor = document.redo().unwrap();
+    assert_eq!(document_rows(&document), vec!["one"]);
+    assert_eq!((cursor.x, cursor.y), (3, 0));
+    // A new edit drops whatever was left to redo.
+    document.insert(&Position { x: 3, y: 0 }, '!').unwrap();
+    assert!(document.redo().is_none());
+    assert_eq!(document_rows(&document), vec!["one!"]);
+}
+
+fn type_text(document: &mut Document, at: Position, text: &str) {
+    for (index, c) in text.chars().enumerate() {
+        let at = Position {
+            x: at.x + index,
+            y: at.y,
+        };
+        document.insert(&at, c).unwrap();
+    }
+}
+
+#[test]
+fn typing_is_undone_a_word_at_a_time() {
+    let mut document = Document::default();
+    type_text(&mut document, Position { x: 0, y: 0 }, "hello");
+    document.undo();
+    assert!(document.is_empty());
+
+    // Moving the cursor, a new line or a pause starts the next group.
+    type_text(&mut document, Position { x: 0, y: 0 }, "ab");
+    document.end_undo_group();
+    type_text(&mut document, Position { x: 2, y: 0 }, "cd");
+    document.insert(&Position { x: 4, y: 0 }, '\n').unwrap();
+    type_text(&mut document, Position { x: 0, y: 1 }, "ef");
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    type_text(&mut document, Position { x: 2, y: 1 }, "gh");
+    let mut steps = Vec::new();
+    while document.undo().is_some() {
+        steps.push(document_rows(&document).join("|"));
+    }
+    assert_eq!(steps, vec!["abcd|ef", "abcd|", "abcd", "ab", ""]);
+}
+
+#[test]
+fn delete_range_within_and_across_rows() {
+    let mut document = Document::default();
+    document
+        .insert_text(&Position { x: 0, y: 0 }, "héllo\nworld\nagain")
+        .unwrap();
+    let removed = document.delete_range(&Position { x: 1, y: 0 }, &Position { x: 4, y: 0 });
+    assert_eq!(removed, "éll");
+    assert_eq!(document_rows(&document), vec!["ho", "world", "again"]);
+
+    let removed = document.delete_range(&Position { x: 1, y: 0 }, &Position { x: 2, y: 2 });
+    assert_eq!(removed, "o\nworld\nag");
+    assert_eq!(document_rows(&document), vec!["hain"]);
+    assert_eq!(document.row(0).unwrap().len(), 4);
+
+    // Backwards or empty ranges remove nothing.
+    let end = Position { x: 3, y: 0 };
+    assert_eq!(document.delete_range(&end, &Position { x: 1, y: 0 }), "");
+    assert_eq!(document.delete_range(&end, &end), "");
+    document.undo();
+    assert_eq!(document_rows(&document), vec!["ho", "world", "again"]);
+}
+
+fn matching_bracket(text: &str, x: usize, y: usize) -> Option<(usize, usize)> {
+    rust_document(text)
+        .find_matching_bracket(&Position { x, y })
+        .map(|position| (position.x, position.y))
+}
+
+#[test]
+fn matching_brackets_respect_nesting() {
+    assert_eq!(matching_bracket("((a)b)", 0, 0), Some((5, 0)));
+    assert_eq!(matching_bracket("((a)b)", 5, 0), Some((0, 0)));
+    assert_eq!(matching_bracket("((a)b)", 1, 0), Some((3, 0)));
+    assert_eq!(matching_bracket("((a)b)", 3, 0), Some((1, 0)));
+    assert_eq!(matching_bracket("((a)b)", 2, 0), None);
+    assert_eq!(matching_bracket("((a)b", 0, 0), None);
+
+    let text = "fn a() {\n    if b {\n        c(\"}\");\n    }\n}";
+    assert_eq!(matching_bracket(text, 7, 0), Some((0, 4)));
+    assert_eq!(matching_bracket(text, 0, 4), Some((7, 0)));
+    assert_eq!(matching_bracket(text, 9, 1), Some((4, 3)));
+    assert_eq!(matching_bracket(text, 4, 3), Some((9, 1)));
+}
+
+#[test]
+fn trimming_on_save_can_be_undone() {
+    let dir = TempDir::new();
+    dir.file(".editorconfig", "[*]\ntrim_trailing_whitespace = true\n");
+    let path = dir.file("a.txt", "a  \nb\t\nc\n");
+    let mut document = Document::open(&path).unwrap();
+    document.insert(&Position { x: 0, y: 2 }, 'x').unwrap();
+    document.save().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\nb\nxc\n");
+    document.undo();
+    assert_eq!(document_rows(&document), vec!["a  ", "b\t", "xc"]);
+    assert!(document.is_dirty());
+    document.undo();
+    assert_eq!(document_rows(&document), vec!["a  ", "b\t", "c"]);
+}
+
+#[test]
+fn saving_keeps_a_file_type_set_after_opening() {
+    let dir = TempDir::new();
+    let path = dir.file("a.txt", "fn a() {}\n");
+    let mut document = Document::open(&path).unwrap();
+    document.set_file_type(FileType::from("a.rs"));
+    document.save().unwrap();
+    assert_eq!(document.file_type(), "Rust");
+    // A new name is a new file, with a type of its own.
+    document.file_name = Some(dir.path("a.py"));
+    document.save().unwrap();
+    assert_eq!(document.file_type(), "Python");
+}
+
+#[test]
+fn listed_matches_overlap_like_highlighted_ones() {
+    let mut document = Document::default();
+    document
+        .insert_text(&Position { x: 0, y: 0 }, "aaa\nxaax")
+        .unwrap();
+    let found: Vec<(usize, usize)> = document
+        .find_all("aa", 10, true)
+        .iter()
+        .map(|position| (position.x, position.y))
+        .collect();
+    assert_eq!(found, vec![(0, 0), (1, 0), (1, 1)]);
+    document.highlight(&Some("aa".to_string()), true, None, None);
+    assert_eq!(matched(&document, 0), "mmm");
+    assert_eq!(document.find_all("aa", 2, true).len(), 2);
+}
+
+#[test]
+fn matches_outside_a_range_are_ignored() {
+    let mut document = Document::default();
+    document
+        .insert_text(&Position { x: 0, y: 0 }, "ab ab\nab ab\nab")
+        .unwrap();
+    document.end_undo_group();
+    let range = (Position { x: 3, y: 0 }, Position { x: 4, y: 1 });
+    let find = |at, direction| {
+        document
+            .find("ab", &at, direction, true, Some(&range))
+            .map(|found| (found.x, found.y))
+    };
+    assert_eq!(
+        find(Position { x: 0, y: 0 }, SearchDirection::Forward),
+        Some((3, 0))
+    );
+    assert_eq!(
+        find(Position { x: 4, y: 0 }, SearchDirection::Forward),
+        Some((0, 1))
+    );
+    // The second match on the row runs past the end of the range.
+    assert_eq!(
+        find(Position { x: 1, y: 1 }, SearchDirection::Forward),
+        None
+    );
+    assert_eq!(
+        find(Position { x: 2, y: 2 }, SearchDirection::Backward),
+        Some((0, 1))
+    );
+    assert_eq!(
+        find(Position { x: 3, y: 0 }, SearchDirection::Backward),
+        None
+    );
+
+    // The end of the range moves along with longer replacements.
+    let range = (Position { x: 3, y: 0 }, Position { x: 5, y: 1 });
+    assert_eq!(document.replace_all("ab", "xyz", true, Some(&range)), 3);
+    assert_eq!(document_rows(&document), vec!["ab xyz", "xyz xyz", "ab"]);
+    document.undo();
+    assert_eq!(document_rows(&document), vec!["ab ab", "ab ab", "ab"]);
+}
+
+#[test]
+fn replace_all_can_ignore_case() {
+    let mut document = Document::default();
+    document
+        .insert_text(&Position { x: 0, y: 0 }, "a \u{212a}K k\nK")
+        .unwrap();
+    // The Kelvin sign matches "k" without case but takes up three bytes.
+    assert_eq!(document.replace_all("k", "m", false, None), 4);
+    assert_eq!(document_rows(&document), vec!["a mm m", "m"]);
+}
+
+#[test]
+fn files_are_read_in_their_encoding() {
+    let dir = TempDir::new();
+    let path = dir.path("a.txt");
+    for (bytes, encoding, newline) in [
+        (&b"\xef\xbb\xbfcaf\xc3\xa9\n"[..], "UTF-8 BOM", &b"\n"[..]),
+        (b"caf\xe9\n", "Latin-1", b"\n"),
+        (b"\xff\xfec\0a\0f\0\xe9\0\n\0", "UTF-16LE", b"\n\0"),
+        (b"\xfe\xff\0c\0a\0f\0\xe9\0\n", "UTF-16BE", b"\0\n"),
+    ] {
+        std::fs::write(&path, bytes).unwrap();
+        let mut document = Document::open(&path).unwrap();
+        assert_eq!(document.encoding(), encoding);
+        let text = document.row(0).unwrap().as_str();
+        assert_eq!(text.trim_start_matches('\u{feff}'), "café", "{encoding}");
+        // Saved again, only the new row is added to the bytes.
+        document.insert(&Position { x: 0, y: 1 }, '\n').unwrap();
+        document.save().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), [bytes, newline].concat());
+    }
+    // Bytes that are control characters in Latin-1 aren't text.
+    std::fs::write(&path, b"\x7fELF\x02\x01\x01\0\xff").unwrap();
+    let err = Document::open(&path).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,592 @@
+mod common;
+
+use common::{cursor, rows, run, run_document, run_typed, typed, TempDir};
+use rtext::{Config, Document, EditorBuilder, Position, Size};
+use termion::event::Key;
+
+#[test]
+fn typing_and_backspace() {
+    let (editor, output) = run_document(
+        Document::default(),
+        vec![
+            Key::Char('a'),
+            Key::Char('b'),
+            Key::Char('c'),
+            Key::Backspace,
+        ],
+    );
+    assert_eq!(rows(&editor), vec!["ab"]);
+    assert_eq!(cursor(&editor), (2, 0));
+    assert!(output.contains("ab"));
+}
+
+#[test]
+fn typing_a_new_line() {
+    let editor = run_typed("ab\ncd", vec![Key::Up, Key::End]);
+    assert_eq!(rows(&editor), vec!["ab", "cd"]);
+    assert_eq!(cursor(&editor), (2, 0));
+}
+
+#[test]
+fn saving_writes_the_file() {
+    let dir = TempDir::new();
+    let path = dir.file("a.txt", "one\ntwo\n");
+    let mut keys = typed("> ");
+    keys.push(Key::Ctrl('s'));
+    let (editor, output) = run_document(Document::open(&path).unwrap(), keys);
+    assert!(!editor.document().is_dirty());
+    assert!(output.contains("File saved successfully."));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "> one\ntwo\n");
+}
+
+#[test]
+fn saving_an_unnamed_buffer_asks_for_a_name() {
+    let dir = TempDir::new();
+    let path = dir.path("new.txt");
+    let mut keys = typed("hi");
+    keys.push(Key::Ctrl('s'));
+    keys.extend(typed(&path));
+    keys.push(Key::Char('\n'));
+    let (editor, _) = run_document(Document::default(), keys);
+    assert_eq!(editor.document().file_name.as_deref(), Some(path.as_str()));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "hi\n");
+}
+
+// The last thing drawn is where the cursor was put on the screen.
+fn screen_cursor(output: &str) -> &str {
+    let end = output.rfind("\u{1b}[?25h").unwrap();
+    let start = output[..end].rfind("\u{1b}[").unwrap();
+    &output[start..end]
+}
+
+#[test]
+fn vertical_movement_through_ragged_lines() {
+    let long = "abcdefghijklmnopqrstuvwxyz0123456789";
+    let document = || {
+        let mut document = Document::default();
+        let text = format!("{long}\nab\n{long}");
+        document
+            .insert_text(&Position { x: 0, y: 0 }, &text)
+            .unwrap();
+        document
+    };
+    let size = Size {
+        width: 20,
+        height: 6,
+    };
+    let builder = || EditorBuilder::new().document(document());
+    let (at_end, end_output) = run(builder(), size, vec![Key::End]);
+    assert_eq!(cursor(&at_end), (36, 0));
+    let (short, _) = run(builder(), size, vec![Key::End, Key::Down]);
+    assert_eq!(cursor(&short), (2, 1));
+    // Coming back to a long line restores the column and the view with it.
+    let (back, back_output) = run(
+        builder(),
+        size,
+        vec![Key::End, Key::Down, Key::Down, Key::Up, Key::Up],
+    );
+    assert_eq!(cursor(&back), (36, 0));
+    assert_eq!(screen_cursor(&back_output), screen_cursor(&end_output));
+}
+
+#[test]
+fn tiny_terminals() {
+    for height in 0..4 {
+        let size = Size { width: 80, height };
+        let mut keys = typed("a\nb");
+        keys.extend([
+            Key::PageUp,
+            Key::PageDown,
+            Key::Alt('z'),
+            Key::F(1),
+            Key::Esc,
+        ]);
+        keys.extend([Key::Up, Key::Down, Key::End]);
+        keys.extend(typed("x"));
+        let (editor, _) = run(EditorBuilder::new(), size, keys);
+        assert_eq!(rows(&editor), vec!["a", "bx"], "height {height}");
+    }
+    let (editor, _) = run(
+        EditorBuilder::new(),
+        Size {
+            width: 0,
+            height: 0,
+        },
+        typed("ok"),
+    );
+    assert_eq!(rows(&editor), vec!["ok"]);
+}
+
+#[test]
+fn quitting_warns_only_about_unsaved_changes() {
+    let dir = TempDir::new();
+    let path = dir.file("a.txt", "a\n");
+    let (_, output) = run_document(Document::open(&path).unwrap(), vec![Key::Ctrl('q')]);
+    assert!(output.contains("Goodbye."));
+    assert!(!output.contains("WARNING!"));
+    let (editor, output) = run_document(
+        Document::open(&path).unwrap(),
+        vec![Key::Char('x'), Key::Ctrl('q')],
+    );
+    assert!(output.contains("WARNING! File has unsaved changes."));
+    assert!(!output.contains("Goodbye."));
+    assert!(editor.document().is_dirty());
+    let (_, output) = run_document(
+        Document::open(&path).unwrap(),
+        vec![Key::Char('x'), Key::Ctrl('s'), Key::Ctrl('q')],
+    );
+    assert!(output.contains("Goodbye."));
+    assert!(!output.contains("WARNING!"));
+}
+
+#[test]
+fn closing_the_file() {
+    let dir = TempDir::new();
+    let path = dir.file("a.txt", "one\n");
+    let (editor, output) = run_document(
+        Document::open(&path).unwrap(),
+        vec![Key::Char('#'), Key::Ctrl('w')],
+    );
+    assert!(output.contains("Save your changes before closing the file."));
+    assert_eq!(editor.document().file_name.as_deref(), Some(path.as_str()));
+    let mut keys = vec![Key::Char('#'), Key::Ctrl('s'), Key::Ctrl('w')];
+    keys.extend(typed("new"));
+    let (editor, output) = run_document(Document::open(&path).unwrap(), keys);
+    assert!(output.contains("Closed the file."));
+    assert!(!output.contains("Goodbye."));
+    assert!(editor.document().file_name.is_none());
+    assert_eq!(rows(&editor), vec!["new"]);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "#one\n");
+}
+
+#[test]
+fn backspace_between_a_pair_deletes_both() {
+    let auto_pairs = || {
+        EditorBuilder::new().config(Config {
+            auto_pairs: true,
+            ..Config::default()
+        })
+    };
+    let (editor, _) = run(
+        auto_pairs(),
+        common::SIZE,
+        vec![Key::Char('('), Key::Backspace],
+    );
+    assert_eq!(rows(&editor), vec![""]);
+    let mut keys = typed("f(\"");
+    keys.extend([Key::Backspace, Key::Backspace]);
+    let (editor, _) = run(auto_pairs(), common::SIZE, keys);
+    assert_eq!(rows(&editor), vec!["f"]);
+    let (editor, _) = run(
+        auto_pairs(),
+        common::SIZE,
+        vec![Key::Char('('), Key::Backspace, Key::Ctrl('z')],
+    );
+    assert_eq!(rows(&editor), vec!["()"]);
+    // Without auto pairs only the opening bracket goes.
+    let editor = run_typed("()", vec![Key::Left, Key::Backspace]);
+    assert_eq!(rows(&editor), vec![")"]);
+}
+
+#[test]
+fn save_as_asks_before_overwriting() {
+    let dir = TempDir::new();
+    let path = dir.file("exists.txt", "old\n");
+    let save_as = |answer: &str| {
+        let mut keys = vec![Key::Char('n'), Key::Ctrl('s')];
+        keys.extend(typed(&format!("{path}\n{answer}\n")));
+        run_document(Document::default(), keys)
+    };
+    let (editor, output) = save_as("n");
+    assert!(output.contains("exists. Overwrite? (y/n)"));
+    assert!(output.contains("Save aborted."));
+    assert!(editor.document().file_name.is_none());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\n");
+    let (editor, _) = save_as("y");
+    assert_eq!(editor.document().file_name.as_deref(), Some(path.as_str()));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "n\n");
+}
+
+#[test]
+fn empty_files_take_typing_right_away() {
+    let dir = TempDir::new();
+    for (content, len) in [("", 0), ("\n", 1), ("\n\n", 2)] {
+        let path = dir.file("empty.txt", content);
+        let document = Document::open(&path).unwrap();
+        assert_eq!(document.len(), len, "{content:?}");
+        let (editor, output) = run_document(document, vec![Key::Char('z')]);
+        assert_eq!(rows(&editor)[0], "z", "{content:?}");
+        assert_eq!(editor.document().len(), len.max(1), "{content:?}");
+        // The welcome message is only for unnamed buffers.
+        assert!(!output.contains("RText editor"), "{content:?}");
+    }
+}
+
+#[test]
+fn edge_markers_show_text_beyond_the_screen() {
+    let long = "x".repeat(100);
+    let open = || {
+        let mut document = Document::default();
+        document
+            .insert_text(&Position { x: 0, y: 0 }, &long)
+            .unwrap();
+        document
+    };
+    let (_, output) = run_document(open(), vec![]);
+    assert!(output.contains('\u{203a}') && !output.contains('\u{2039}'));
+    let (_, output) = run_document(open(), vec![Key::End]);
+    assert!(output.contains('\u{2039}'));
+
+    let config = Config {
+        edge_markers: false,
+        ..Config::default()
+    };
+    let builder = EditorBuilder::new().document(open()).config(config);
+    let (_, output) = run(builder, common::SIZE, vec![Key::End]);
+    assert!(!output.contains('\u{203a}') && !output.contains('\u{2039}'));
+}
+
+#[test]
+fn editing_in_the_middle_of_a_prompt() {
+    let dir = TempDir::new();
+    let mut keys = typed("hi");
+    keys.push(Key::Ctrl('s'));
+    // "bdx" becomes "abcd" through edits away from the end of the input.
+    keys.extend(typed("bdx"));
+    keys.extend([Key::Left, Key::Delete, Key::Left, Key::Char('c')]);
+    keys.extend([Key::Home, Key::Char('a'), Key::Home]);
+    keys.extend(typed(&dir.path("")));
+    keys.extend([Key::End, Key::Char('é'), Key::Backspace, Key::Char('\n')]);
+    let (editor, _) = run_document(Document::default(), keys);
+    let path = dir.path("abcd");
+    assert_eq!(editor.document().file_name.as_deref(), Some(path.as_str()));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "hi\n");
+}
+
+#[test]
+fn saving_as_latin_1() {
+    let dir = TempDir::new();
+    let path = dir.file("a.txt", "caf\n");
+    let save_as = |encoding: &str| {
+        let mut keys = vec![Key::Alt('E')];
+        keys.extend(typed(encoding));
+        keys.push(Key::Char('\n'));
+        keys
+    };
+    let mut keys = vec![Key::End, Key::Char('é')];
+    keys.extend(save_as("latin-1"));
+    let (editor, _) = run_document(Document::open(&path).unwrap(), keys);
+    assert_eq!(std::fs::read(&path).unwrap(), b"caf\xe9\n");
+    assert_eq!(editor.document().encoding(), "Latin-1");
+    // Reopened, it reads as the same text, and saves back as Latin-1.
+    let (editor, _) = run_document(
+        Document::open(&path).unwrap(),
+        vec![Key::Char('x'), Key::Ctrl('s')],
+    );
+    assert_eq!(rows(&editor), vec!["xcafé"]);
+    assert_eq!(editor.document().encoding(), "Latin-1");
+    assert_eq!(std::fs::read(&path).unwrap(), b"xcaf\xe9\n");
+
+    // Text Latin-1 can't hold fails the save and keeps the old encoding.
+    let path = dir.file("b.txt", "caf\n");
+    let mut keys = vec![Key::End, Key::Char('€')];
+    keys.extend(save_as("latin-1"));
+    let (editor, _) = run_document(Document::open(&path).unwrap(), keys);
+    assert_eq!(std::fs::read(&path).unwrap(), b"caf\n");
+    assert_eq!(editor.document().encoding(), "UTF-8");
+
+    let mut keys = typed("x");
+    keys.extend(save_as("utf-16le"));
+    keys.push(Key::Esc);
+    let (editor, _) = run_document(Document::default(), keys);
+    assert_eq!(editor.document().encoding(), "UTF-8");
+}
+
+#[test]
+fn text_fills_the_rows_the_bars_leave() {
+    let text: Vec<String> = (0..20).map(|line| line.to_string()).collect();
+    for bar_rows in 1..=3_u16 {
+        let mut document = Document::default();
+        document
+            .insert_text(&Position { x: 0, y: 0 }, &text.join("\n"))
+            .unwrap();
+        let config = Config {
+            bar_rows,
+            ..Config::default()
+        };
+        let builder = EditorBuilder::new().document(document).config(config);
+        let (editor, output) = run(builder, common::SIZE, vec![Key::Down; 19]);
+        assert_eq!(cursor(&editor), (0, 19));
+        // Scrolling keeps the last line just above the bars.
+        let last_text_row = common::SIZE.height - bar_rows;
+        assert!(
+            screen_cursor(&output).starts_with(&format!("\u{1b}[{last_text_row};")),
+            "{bar_rows} bar rows"
+        );
+    }
+}
+
+#[test]
+fn enter_continues_rust_line_comments() {
+    let dir = TempDir::new();
+    let path = dir.file("a.rs", "fn a() {\n    // one\n}\n");
+    let config = |continue_comments| Config {
+        continue_comments,
+        ..Config::default()
+    };
+    let keys = || {
+        let mut keys = vec![Key::Down, Key::End, Key::Char('\n')];
+        keys.extend(typed("two\n\nx"));
+        keys
+    };
+    let builder = EditorBuilder::new()
+        .document(Document::open(&path).unwrap())
+        .config(config(true));
+    let (editor, _) = run(builder, common::SIZE, keys());
+    // An empty comment line ends the comment instead of continuing it.
+    assert_eq!(
+        rows(&editor),
+        vec!["fn a() {", "    // one", "    // two", "    x", "}"]
+    );
+
+    let builder = EditorBuilder::new()
+        .document(Document::open(&path).unwrap())
+        .config(config(false));
+    let (editor, _) = run(builder, common::SIZE, keys());
+    assert_eq!(
+        rows(&editor),
+        vec!["fn a() {", "    // one", "two", "", "x", "}"]
+    );
+}
+
+#[test]
+fn overwriting_undoes_in_one_step() {
+    let document = || {
+        let mut document = Document::default();
+        document
+            .insert_text(&Position { x: 0, y: 0 }, "abcd")
+            .unwrap();
+        document.end_undo_group();
+        document
+    };
+    let (editor, _) = run_document(document(), vec![Key::Insert, Key::Char('x')]);
+    assert_eq!(rows(&editor), vec!["xbcd"]);
+    assert_eq!(cursor(&editor), (1, 0));
+    let (editor, _) = run_document(
+        document(),
+        vec![Key::Insert, Key::Char('x'), Key::Ctrl('z')],
+    );
+    assert_eq!(rows(&editor), vec!["abcd"]);
+    // Past the end of the row overwriting appends, still as typing.
+    let mut keys = vec![Key::Insert, Key::Right, Key::Right];
+    keys.extend(typed("xyzw"));
+    keys.push(Key::Ctrl('z'));
+    let (editor, _) = run_document(document(), keys);
+    assert_eq!(rows(&editor), vec!["abcd"]);
+}
+
+fn search(text: &str, keys: Vec<Key>) -> (usize, usize) {
+    let mut document = Document::default();
+    document
+        .insert_text(&Position { x: 0, y: 0 }, text)
+        .unwrap();
+    let (editor, _) = run_document(document, keys);
+    cursor(&editor)
+}
+
+fn find(query: &str) -> Vec<Key> {
+    let mut keys = vec![Key::Ctrl('f')];
+    keys.extend(typed(query));
+    keys.push(Key::Char('\n'));
+    keys
+}
+
+#[test]
+fn search_ignores_case_unless_the_query_has_capitals() {
+    assert_eq!(search("xx\nFoo", find("foo")), (0, 1));
+    assert_eq!(search("foo\nFoo", find("Foo")), (0, 1));
+}
+
+#[test]
+fn matching_case_lasts_for_one_search() {
+    let mut keys = vec![Key::Ctrl('f')];
+    keys.extend(typed("foo"));
+    keys.extend([Key::Alt('c'), Key::Char('\n')]);
+    assert_eq!(search("Foo\nfoo", keys.clone()), (0, 1));
+    keys.push(Key::Up);
+    keys.extend(find("foo"));
+    assert_eq!(search("Foo\nfoo", keys), (0, 0));
+}
+
+#[test]
+fn replacing_one_by_one_is_one_undo_step() {
+    // The Kelvin sign matches "k" without case but takes up three bytes.
+    let text = "a \u{212a} b K\nk k";
+    let replace = |answers: &str| {
+        let mut keys = vec![Key::Ctrl('r')];
+        keys.extend(typed("k\nmm\n"));
+        keys.extend(typed(answers));
+        keys
+    };
+    let document = || {
+        let mut document = Document::default();
+        document
+            .insert_text(&Position { x: 0, y: 0 }, text)
+            .unwrap();
+        document.end_undo_group();
+        document
+    };
+    let (editor, _) = run_document(document(), replace("yna"));
+    assert_eq!(rows(&editor), vec!["a mm b K", "mm mm"]);
+
+    let mut keys = replace("yna");
+    keys.push(Key::Ctrl('z'));
+    let (editor, _) = run_document(document(), keys);
+    assert_eq!(rows(&editor), vec!["a \u{212a} b K", "k k"]);
+}
+
+#[test]
+fn searching_for_a_tab_finds_tab_indentation() {
+    let keys = vec![Key::Ctrl('f'), Key::Char('\t'), Key::Char('\n')];
+    assert_eq!(
+        search("fn a() {\n    b();\n\tc();\n}", keys.clone()),
+        (0, 2)
+    );
+    assert_eq!(search("x\tb", keys), (1, 0));
+}
+
+fn open(path: &str) -> Vec<Key> {
+    let mut keys = vec![Key::Ctrl('o')];
+    keys.extend(typed(path));
+    keys.push(Key::Char('\n'));
+    keys
+}
+
+#[test]
+fn closing_the_middle_of_three_buffers() {
+    let dir = TempDir::new();
+    let paths: Vec<String> = ["a", "b", "c"]
+        .iter()
+        .map(|name| dir.file(name, &format!("{name}1\n{name}2\n")))
+        .collect();
+    let mut keys = open(&paths[0]);
+    keys.push(Key::Down);
+    keys.extend(open(&paths[1]));
+    keys.extend(open(&paths[2]));
+    keys.extend([Key::Alt(','), Key::Ctrl('w')]);
+    let (editor, output) = run_document(Document::default(), keys.clone());
+    // The empty buffer it started with gave way to the first file.
+    assert!(output.contains("[3/3]"));
+    assert_eq!(
+        editor.document().file_name.as_deref(),
+        Some(paths[0].as_str())
+    );
+    assert_eq!(cursor(&editor), (0, 1));
+
+    keys.push(Key::Alt('.'));
+    let (editor, _) = run_document(Document::default(), keys.clone());
+    assert_eq!(
+        editor.document().file_name.as_deref(),
+        Some(paths[2].as_str())
+    );
+    keys.extend([Key::Alt('.'), Key::Ctrl('w'), Key::Ctrl('w')]);
+    let (editor, _) = run_document(Document::default(), keys);
+    assert!(editor.document().file_name.is_none());
+    assert!(editor.document().is_empty());
+}
+
+#[test]
+fn opening_an_open_file_switches_to_it() {
+    let dir = TempDir::new();
+    let a = dir.file("a.txt", "a\n");
+    let b = dir.file("b.txt", "b\n");
+    let mut keys = open(&a);
+    keys.push(Key::Char('x'));
+    keys.extend(open(&b));
+    keys.extend(open(&a));
+    let (editor, output) = run_document(Document::default(), keys);
+    assert_eq!(rows(&editor), vec!["xa"]);
+    assert!(output.contains(&format!("Switched to '{a}'.")));
+}
+
+#[test]
+fn edit_locations_belong_to_their_buffer() {
+    let dir = TempDir::new();
+    let a = dir.file("a.txt", "a\n");
+    let b = dir.file("b.txt", "b\n");
+    let mut keys = open(&a);
+    keys.push(Key::Char('x'));
+    keys.extend(open(&b));
+    keys.push(Key::Alt('e'));
+    let (_, output) = run_document(Document::default(), keys.clone());
+    assert!(output.contains("No edits yet."));
+    keys.extend([Key::Alt(','), Key::End, Key::Alt('e')]);
+    let (editor, _) = run_document(Document::default(), keys);
+    assert_eq!(cursor(&editor), (1, 0));
+}
+
+#[test]
+fn quitting_lists_the_unsaved_buffers() {
+    let dir = TempDir::new();
+    let paths: Vec<String> = ["a", "b", "c"]
+        .iter()
+        .map(|name| dir.file(name, &format!("{name}\n")))
+        .collect();
+    let mut keys = open(&paths[0]);
+    keys.push(Key::Char('x'));
+    keys.extend(open(&paths[1]));
+    keys.extend(open(&paths[2]));
+    keys.push(Key::Char('x'));
+    keys.extend(open(&paths[1]));
+    keys.push(Key::Ctrl('q'));
+    let (editor, output) = run_document(Document::default(), keys.clone());
+    assert!(output.contains("WARNING! 2 unsaved buffers (a, c)."));
+    assert!(!output.contains("Goodbye."));
+    assert!(!editor.document().is_dirty());
+
+    keys.pop();
+    keys.extend([Key::Alt('.'), Key::Ctrl('s'), Key::Ctrl('q')]);
+    let (_, output) = run_document(Document::default(), keys.clone());
+    assert!(output.contains("WARNING! 1 unsaved buffer (a)."));
+
+    keys.pop();
+    keys.extend([Key::Alt('.'), Key::Ctrl('s'), Key::Ctrl('q')]);
+    let (_, output) = run_document(Document::default(), keys);
+    assert!(output.contains("Goodbye."));
+    assert!(!output.contains("WARNING!"));
+}
+
+#[test]
+fn replacing_all_matches_case_like_search() {
+    let replace_all = |query: &str| {
+        let mut keys = vec![Key::Alt('r')];
+        keys.extend(typed(query));
+        keys.push(Key::Char('\n'));
+        keys.extend(typed("x\n"));
+        rows(&run_typed("Foo foo FOO fOo", keys))
+    };
+    assert_eq!(replace_all("foo"), vec!["x x x x"]);
+    assert_eq!(replace_all("Foo"), vec!["x foo FOO fOo"]);
+    assert_eq!(replace_all("fOo"), vec!["Foo foo FOO x"]);
+}
+
+#[test]
+fn expanding_a_snippet_is_one_undo_step() {
+    let expand = |keys: Vec<Key>| {
+        let builder = EditorBuilder::new().config(Config {
+            snippets: vec![("fn".to_string(), "fn ${name}() {\n}".to_string())],
+            ..Config::default()
+        });
+        let mut all = typed("a fn");
+        all.push(Key::Left);
+        all.push(Key::Right);
+        all.extend(keys);
+        run(builder, common::SIZE, all).0
+    };
+    let editor = expand(vec![Key::Alt('i')]);
+    assert_eq!(rows(&editor), vec!["a fn () {", "}"]);
+    assert_eq!(cursor(&editor), (5, 0));
+    let editor = expand(vec![Key::Alt('i'), Key::Ctrl('z')]);
+    assert_eq!(rows(&editor), vec!["a fn"]);
+    assert_eq!(cursor(&editor), (2, 0));
+}
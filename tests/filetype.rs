@@ -0,0 +1,99 @@
+mod common;
+
+use common::TempDir;
+use rtext::{Document, FileType};
+
+fn shebang(line: &str) -> Option<String> {
+    FileType::from_shebang(line).map(|file_type| file_type.name())
+}
+
+#[test]
+fn file_type_from_shebang() {
+    assert_eq!(shebang("#!/bin/sh").as_deref(), Some("Shell"));
+    assert_eq!(shebang("#!/usr/bin/env python").as_deref(), Some("Python"));
+    assert_eq!(
+        shebang("#!/usr/bin/env -S python3 -u").as_deref(),
+        Some("Python")
+    );
+    assert_eq!(shebang("import os"), None);
+    assert_eq!(shebang("#!/usr/bin/perl"), None);
+}
+
+#[test]
+fn shebang_wins_over_the_extension() {
+    assert_eq!(
+        FileType::detect("deploy", Some("#!/bin/sh")).name(),
+        "Shell"
+    );
+    assert_eq!(
+        FileType::detect("a.rs", Some("fn main() {}")).name(),
+        "Rust"
+    );
+    assert_eq!(FileType::detect("deploy", None).name(), "No filetype");
+}
+
+#[test]
+fn open_detects_extensionless_scripts() {
+    let dir = TempDir::new();
+    let path = dir.file("deploy", "#!/usr/bin/env python3\nprint(1)\n");
+    assert_eq!(Document::open(&path).unwrap().file_type(), "Python");
+    let path = dir.file("notes", "just text\n");
+    assert_eq!(Document::open(&path).unwrap().file_type(), "No filetype");
+}
+
+#[test]
+fn file_type_from_the_file_name() {
+    for (file_name, name) in [
+        ("main.rs", "Rust"),
+        ("MAIN.RS", "Rust"),
+        ("src/Lib.Rs", "Rust"),
+        ("Cargo.lock", "TOML"),
+        (".gitignore", "Ignore"),
+        ("project/.bashrc", "Shell"),
+        ("Makefile", "Makefile"),
+        ("Dockerfile", "Dockerfile"),
+        ("Makefile.bak", "No filetype"),
+        ("README", "No filetype"),
+        (".rs", "No filetype"),
+    ] {
+        assert_eq!(FileType::from(file_name).name(), name, "{file_name}");
+    }
+}
+
+#[test]
+fn python_files() {
+    for file_name in ["main.py", "stubs.pyi", "gui.pyw"] {
+        assert_eq!(FileType::from(file_name).name(), "Python", "{file_name}");
+    }
+    let python = FileType::from("main.py");
+    let options = python.highlighting_options();
+    assert!(options.numbers() && options.strings() && options.comments());
+    assert_eq!(options.comment_starts(), &vec!["#".to_string()]);
+}
+
+#[test]
+fn interpreters_named_by_the_shebang() {
+    assert_eq!(shebang("#!/bin/bash").as_deref(), Some("Shell"));
+    assert_eq!(shebang("#!/usr/bin/python").as_deref(), Some("Python"));
+    assert_eq!(shebang("#! /usr/bin/python3.11").as_deref(), Some("Python"));
+    assert_eq!(shebang("#!/usr/bin/env lua5.4").as_deref(), Some("Lua"));
+    let dir = TempDir::new();
+    let path = dir.file("build", "#!/bin/bash\nmake\n");
+    assert_eq!(Document::open(&path).unwrap().file_type(), "Shell");
+}
+
+#[test]
+fn file_type_from_the_first_lines() {
+    let from_content =
+        |lines: &[&str]| FileType::from_content(lines).map(|file_type| file_type.name());
+    assert_eq!(
+        from_content(&["#!/bin/bash", "make"]).as_deref(),
+        Some("Shell")
+    );
+    assert_eq!(
+        from_content(&["#!/usr/bin/python"]).as_deref(),
+        Some("Python")
+    );
+    assert_eq!(from_content(&["", "#!/bin/sh"]), None);
+    assert_eq!(from_content(&[]), None);
+}
@@ -0,0 +1,183 @@
+use rtext::{Continuation, FileType, HighlightType, Highlighter, Row};
+use termion::color::Fg;
+
+// One letter per char, so a row's highlighting reads like the row itself.
+fn kinds(file_name: &str, text: &str) -> String {
+    let file_type = FileType::from(file_name);
+    let (highlighting, _) = file_type
+        .highlighter()
+        .highlight_row(text, Continuation::None);
+    highlighting
+        .iter()
+        .map(|kind| match kind {
+            HighlightType::Number => 'n',
+            HighlightType::String => 's',
+            HighlightType::Character => 'c',
+            HighlightType::Comment | HighlightType::MultilineComment => '#',
+            HighlightType::PrimaryKeywords => 'p',
+            HighlightType::SecondaryKeywords => 'k',
+            HighlightType::Section => 'S',
+            HighlightType::Key => 'K',
+            HighlightType::Variable => 'v',
+            _ => '.',
+        })
+        .collect()
+}
+
+#[test]
+fn toml_sections_keys_and_values() {
+    assert_eq!(kinds("a.toml", "[package]"), "SSSSSSSSS");
+    assert_eq!(kinds("a.toml", "name = \"rtext\""), "KKKK...sssssss");
+    assert_eq!(kinds("a.toml", "jobs = 4"), "KKKK...n");
+    assert_eq!(kinds("a.toml", "# name = 1"), "##########");
+    assert_eq!(kinds("a.ini", "; note"), "######");
+}
+
+#[test]
+fn toml_literal_strings_have_no_escapes() {
+    assert_eq!(kinds("a.toml", r"path = 'C:\' # x"), r"KKKK...sssss.###");
+    assert_eq!(kinds("a.toml", r#"s = "a\"b" # x"#), r#"K...ssssss.###"#);
+}
+
+#[test]
+fn shell_variables_in_double_quoted_strings() {
+    assert_eq!(kinds("a.sh", r#"echo "hi $USER!""#), "kkkk.ssssvvvvvss");
+    assert_eq!(kinds("a.sh", r#"x="${HOME}/bin""#), "..svvvvvvvsssss");
+    assert_eq!(kinds("a.sh", "echo '$USER'"), "kkkk.sssssss");
+    assert_eq!(kinds("a.sh", "cd $DIR # go"), "kk.vvvv.####");
+}
+
+#[test]
+fn shell_keywords_and_shebang() {
+    assert_eq!(kinds("a.sh", "if true; then fi"), "pp.......pppp.pp");
+    assert_eq!(kinds("a.sh", "#!/bin/sh"), "#########");
+}
+
+#[test]
+fn word_characters_per_file_type() {
+    let css = FileType::from("a.css");
+    let rust = FileType::from("a.rs");
+    assert!(css.highlighting_options().is_word_char('-'));
+    assert!(!rust.highlighting_options().is_word_char('-'));
+    assert!(rust.highlighting_options().is_word_char('_'));
+    // `@media-x` is one word in CSS, so it is no keyword.
+    assert_eq!(kinds("a.css", "@media-x"), "........");
+    assert_eq!(kinds("a.css", "@media x"), "pppppp..");
+    assert_eq!(kinds("a.rs", "x-fn-y"), "..pp..");
+}
+
+// Threads the continuation from row to row like the document does.
+fn kinds_of_rows(file_name: &str, rows: &[&str]) -> Vec<String> {
+    let file_type = FileType::from(file_name);
+    let mut continuation = Continuation::None;
+    rows.iter()
+        .map(|text| {
+            let (highlighting, next) = file_type.highlighter().highlight_row(text, continuation);
+            continuation = next;
+            highlighting
+                .iter()
+                .map(|kind| {
+                    if *kind == HighlightType::String {
+                        's'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn rust_raw_strings() {
+    // The backslash doesn't escape, so the quote after it ends the string
+    // and the last one opens another.
+    assert_eq!(kinds("a.rs", r#"r"a\"b""#), "sssss.s");
+    assert_eq!(kinds("a.rs", r##"x = r#"a "b" c"#;"##), "....ssssssssssss.");
+    assert_eq!(kinds("a.rs", r#"br"\" x"#), "sssss..");
+    assert_eq!(
+        kinds_of_rows("a.rs", &[r##"let s = r#"one"##, r##"two "# x"##, "\"y\""]),
+        vec!["........ssssss", "ssssss..", "sss"]
+    );
+}
+
+#[test]
+fn number_forms() {
+    assert_eq!(kinds("a.rs", "0xFF 0b1010 0o77"), "nnnn.nnnnnn.nnnn");
+    assert_eq!(
+        kinds("a.rs", "1_000_000 1.5e-3 2E10"),
+        "nnnnnnnnn.nnnnnn.nnnn"
+    );
+    assert_eq!(kinds("a.rs", "x0x1 1..5"), ".....n..n");
+    assert_eq!(kinds("a.c", "0x1f;"), "nnnn.");
+}
+
+// Digits are numbers, and a row ending in a backslash turns the next one
+// into a comment.
+struct Digits;
+
+impl Highlighter for Digits {
+    fn highlight_row(&self, text: &str, start: Continuation) -> (Vec<HighlightType>, Continuation) {
+        let highlighting = text
+            .chars()
+            .map(|c| match c {
+                _ if start == Continuation::Comment => HighlightType::Comment,
+                '0'..='9' => HighlightType::Number,
+                _ => HighlightType::None,
+            })
+            .collect();
+        let continuation = if text.ends_with('\\') {
+            Continuation::Comment
+        } else {
+            Continuation::None
+        };
+        (highlighting, continuation)
+    }
+}
+
+// Marks the chars a rendered row draws in the number or comment color.
+fn colors(row: &Row) -> String {
+    let number = format!("{}", Fg(HighlightType::Number.to_color()));
+    let comment = format!("{}", Fg(HighlightType::Comment.to_color()));
+    let rendered = row.render(0, 80, None, None, None);
+    let mut marks = String::new();
+    let mut mark = '.';
+    let mut rest = rendered.as_str();
+    while let Some(c) = rest.chars().next() {
+        if c == '\u{1b}' {
+            let end = rest.find('m').unwrap() + 1;
+            mark = match &rest[..end] {
+                color if color == number => 'n',
+                color if color == comment => '#',
+                _ => '.',
+            };
+            rest = &rest[end..];
+        } else {
+            marks.push(mark);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    marks
+}
+
+#[test]
+fn rows_highlight_through_any_highlighter() {
+    let mut first = Row::from("a1 22\\");
+    let mut second = Row::from("b3");
+    let continuation = first.highlight(&Digits, &None, true, None, Continuation::None);
+    assert_eq!(continuation, Continuation::Comment);
+    assert_eq!(colors(&first), ".n.nn.");
+    second.highlight(&Digits, &None, true, None, continuation);
+    assert_eq!(colors(&second), "##");
+    // The document marks edited rows for highlighting again.
+    second.insert(2, '4');
+    second.is_highlighted = false;
+    second.highlight(&Digits, &None, true, None, Continuation::None);
+    assert_eq!(colors(&second), ".nn");
+}
+
+#[test]
+fn python_hash_comments() {
+    assert_eq!(kinds("a.py", "x = 1 # one"), "....n.#####");
+    assert_eq!(kinds("a.py", "s = '#'"), "....sss");
+}
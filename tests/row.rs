@@ -0,0 +1,141 @@
+use rtext::{Continuation, FileType, HighlightType, Row, SearchDirection};
+use termion::color::Fg;
+use unicode_segmentation::UnicodeSegmentation;
+
+fn assert_len(row: &Row) {
+    assert_eq!(row.len(), row.as_str().graphemes(true).count(), "{row:?}");
+}
+
+#[test]
+fn edits_keep_the_length_in_sync() {
+    let mut row = Row::from("ab".repeat(1000).as_str());
+    row.insert(1000, 'é');
+    row.insert(row.len(), 'e');
+    row.insert(row.len(), '\u{301}');
+    assert_len(&row);
+    assert!(row.as_str().ends_with("be\u{301}"));
+    row.delete(1000);
+    row.delete(0);
+    assert_len(&row);
+    assert_eq!(row.len(), 2000);
+}
+
+#[test]
+fn quote_pairs_around_the_cursor() {
+    let file_type = FileType::from("a.sh");
+    let opts = file_type.highlighting_options();
+    let row = Row::from(r#"x "a 'b' c" y"#);
+    assert_eq!(row.quote_pair(4, opts), Some((2, 10)));
+    // The single quotes are inside the string, so they don't pair up.
+    assert_eq!(row.quote_pair(6, opts), Some((2, 10)));
+    assert_eq!(row.quote_pair(11, opts), Some((2, 10)));
+    assert_eq!(row.quote_pair(0, opts), None);
+    let row = Row::from(r#"x "a\"b" y"#);
+    assert_eq!(row.quote_pair(3, opts), Some((2, 7)));
+    let row = Row::from(r#"x "abc"#);
+    assert_eq!(row.quote_pair(3, opts), None);
+}
+
+// Drops the color escapes `render` adds, leaving the text that was drawn.
+fn strip_colors(rendered: &str) -> String {
+    let mut parts = rendered.split('\u{1b}');
+    let mut text = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        text.push_str(part.split_once('m').map_or("", |(_, rest)| rest));
+    }
+    text
+}
+
+#[test]
+fn control_characters_are_drawn_in_caret_notation() {
+    let row = Row::from("a\u{1b}b\u{b}c\u{9b}d");
+    let text = strip_colors(&row.render(0, 80, None, None, None));
+    assert_eq!(text, "a^[b^Kc<9b>d");
+    assert!(!text.chars().any(char::is_control));
+    assert_eq!(row.columns(0, 3), 4);
+    // A caret pair that doesn't fit is left out rather than cut in half.
+    assert_eq!(strip_colors(&row.render(0, 2, None, None, None)), "a");
+}
+
+#[test]
+fn find_multi_byte_text() {
+    let row = Row::from("le café est très bon 👍🏽 ok e\u{301}t");
+    let find = |query, at| row.find(query, at, SearchDirection::Forward, true);
+    assert_eq!(find("café", 0), Some(3));
+    assert_eq!(find("très", 0), Some(12));
+    assert_eq!(find("👍🏽", 0), Some(21));
+    assert_eq!(find("ok", 0), Some(23));
+    // Half a grapheme is no match.
+    assert_eq!(find("👍", 0), None);
+    assert_eq!(find("e", 26), None);
+    assert_eq!(find("t", 26), Some(27));
+    let find_back = |query, at| row.find(query, at, SearchDirection::Backward, true);
+    assert_eq!(find_back("é", 28), Some(6));
+    assert_eq!(find_back("bon", 23), Some(17));
+    assert_eq!(find_back("bon", 19), None);
+}
+
+#[test]
+fn insert_anywhere_in_the_row() {
+    for (text, at, c, expected) in [
+        ("abc", 0, 'x', "xabc"),
+        ("abc", 1, 'x', "axbc"),
+        ("abc", 2, 'x', "abxc"),
+        ("abc", 3, 'x', "abcx"),
+        ("aé😀c", 0, 'x', "xaé😀c"),
+        ("aé😀c", 2, 'ü', "aéü😀c"),
+        ("aé😀c", 3, 'é', "aé😀éc"),
+        ("aé😀c", 4, 'x', "aé😀cx"),
+        ("ae", 2, '\u{301}', "ae\u{301}"),
+        ("ae", 1, '\u{301}', "a\u{301}e"),
+        // ASCII merges with a combining mark after it, and a '\n' with a
+        // '\r' before it.
+        ("\u{301}", 0, 'a', "a\u{301}"),
+        ("ab\u{301}", 1, 'x', "axb\u{301}"),
+        ("ab\u{301}", 2, 'x', "ab\u{301}x"),
+        ("a\r", 2, '\n', "a\r\n"),
+        (
+            "\u{1f468}\u{1f469}",
+            1,
+            '\u{200d}',
+            "\u{1f468}\u{200d}\u{1f469}",
+        ),
+    ] {
+        let mut row = Row::from(text);
+        row.insert(at, c);
+        assert_eq!(row.as_str(), expected);
+        assert_eq!(row.len(), expected.graphemes(true).count(), "{expected}");
+    }
+}
+
+#[test]
+fn match_length_is_the_text_matched() {
+    let row = Row::from("a \u{212a}b é");
+    assert_eq!(row.match_len(2, "kB", false), Some(2));
+    assert_eq!(row.match_len(2, "kB", true), None);
+    assert_eq!(row.match_len(5, "É", false), Some(1));
+    assert_eq!(row.match_len(0, "b", false), None);
+}
+
+#[test]
+fn a_match_cut_by_the_render_range_keeps_its_color() {
+    let file_type = FileType::from("a.txt");
+    let mut row = Row::from("xx abcdef yy");
+    let word = Some("abcdef".to_string());
+    row.highlight(
+        file_type.highlighter(),
+        &word,
+        true,
+        None,
+        Continuation::None,
+    );
+    let match_color = format!("{}", Fg(HighlightType::Match.to_color()));
+    let first = row.render(3, 6, None, None, None);
+    let second = row.render(6, 9, None, None, None);
+    assert!(first.starts_with(&match_color), "{first:?}");
+    assert!(second.starts_with(&match_color), "{second:?}");
+    assert_eq!(strip_colors(&first), "abc");
+    assert_eq!(strip_colors(&second), "def");
+    // A range running past the end of the row stops at the end.
+    assert_eq!(strip_colors(&row.render(9, 100, None, None, None)), " yy");
+}